@@ -40,14 +40,55 @@ fn join(arr: &Vec<String>, with: &str) -> String {
   result
 }
 
-fn valid_pattern(pattern: &String) -> bool {
-  let pattern = pattern.replace(".", "\\.").replace("*", ".*");
-  let regex = regex::Regex::new(pattern.as_str());
-  if let Err(_) = regex {
-    false
-  } else {
-    true
+// the value of `#[attr = "..."]`
+fn str_attr(attr: &syn::Attribute) -> Option<String> {
+  match attr.parse_meta() {
+    Ok(syn::Meta::NameValue(syn::MetaNameValue {
+      lit: syn::Lit::Str(lit),
+      ..
+    })) => Some(lit.value()),
+    _ => None,
+  }
+}
+
+// names can have placeholders like `{app}`, `{{` and `}}` are literal braces,
+// must accept the same names as fs_pro::shape::params
+fn valid_name(name: &str) -> Result<(), &'static str> {
+  if name.is_empty() {
+    return Err("name cannot be empty");
+  }
+  if name.contains('/') {
+    return Err("name cannot contain '/'");
   }
+  let mut chars = name.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '{' if chars.peek() == Some(&'{') => {
+        chars.next();
+      }
+      '}' if chars.peek() == Some(&'}') => {
+        chars.next();
+      }
+      '{' => {
+        let mut empty = true;
+        loop {
+          match chars.next() {
+            Some('}') if empty => return Err("empty placeholder in name"),
+            Some('}') => break,
+            Some('{') | None => return Err("unclosed placeholder in name"),
+            Some(_) => empty = false,
+          }
+        }
+      }
+      '}' => return Err("unmatched '}' in name, use '}}'"),
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+fn valid_pattern(pattern: &String) -> bool {
+  fs_pro_pattern::compile(pattern).is_ok()
 }
 
 type ParseResult = Vec<(
@@ -56,23 +97,158 @@ type ParseResult = Vec<(
   Option<String>,
   Option<String>,
   Option<Vec<String>>,
+  ItemOptions,
 )>;
 
-fn to_string(result: ParseResult, struct_name: String) -> String {
+// the parsed attributes that end up in fs_pro::shape::ShapeItemOptions
+#[derive(Default)]
+struct ItemOptions {
+  optional: bool,
+  content: Option<String>,
+  validators: Vec<String>,
+  mode: Option<u32>,
+  executable: bool,
+  readonly: bool,
+}
+
+impl ItemOptions {
+  fn render(&self) -> String {
+    format!(
+      "::fs_pro::shape::ShapeItemOptions {{ optional: {}, content: {}, validators: ::std::borrow::Cow::Borrowed(&[{}]), mode: ::fs_pro::shape::ModeRequirement {{ mode: {:?}, executable: {}, readonly: {} }} }}",
+      self.optional,
+      match &self.content {
+        Some(content) => format!("Some(::std::borrow::Cow::Borrowed({}))", content),
+        None => "None".to_string(),
+      },
+      self
+        .validators
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", "),
+      self.mode,
+      self.executable,
+      self.readonly
+    )
+  }
+}
+
+// parses a content check attribute (#[max_size = 1024], #[non_empty], #[validate_with = "f"], ...)
+// to the expression of the ContentValidator it sets, returns None if attr is not a content check
+// and Some(None) if it's malformed
+fn validator_attr(attr: &syn::Attribute) -> Option<Option<String>> {
+  let name = [
+    "max_size",
+    "min_size",
+    "non_empty",
+    "utf8",
+    "json",
+    "validate_with",
+  ]
+  .iter()
+  .find(|name| attr.path.is_ident(name))?;
+  let validator = match (*name, attr.parse_meta()) {
+    (
+      "max_size",
+      Ok(syn::Meta::NameValue(syn::MetaNameValue {
+        lit: syn::Lit::Int(lit),
+        ..
+      })),
+    ) => lit
+      .base10_parse::<u64>()
+      .ok()
+      .map(|size| format!("MaxSize({})", size)),
+    (
+      "min_size",
+      Ok(syn::Meta::NameValue(syn::MetaNameValue {
+        lit: syn::Lit::Int(lit),
+        ..
+      })),
+    ) => lit
+      .base10_parse::<u64>()
+      .ok()
+      .map(|size| format!("MinSize({})", size)),
+    ("non_empty", Ok(syn::Meta::Path(_))) => Some("NonEmpty".to_string()),
+    ("utf8", Ok(syn::Meta::Path(_))) => Some("Utf8".to_string()),
+    // the Json variant only exists with the json feature of fs_pro, the macro fails
+    // to compile with a clear message without it
+    ("json", Ok(syn::Meta::Path(_))) => {
+      return Some(Some("::fs_pro::__json_validator!()".to_string()))
+    }
+    (
+      "validate_with",
+      Ok(syn::Meta::NameValue(syn::MetaNameValue {
+        lit: syn::Lit::Str(lit),
+        ..
+      })),
+    ) => match lit.parse::<syn::Path>() {
+      Ok(_) => Some(format!("With({})", lit.value())),
+      Err(_) => None,
+    },
+    _ => None,
+  };
+  if validator.is_none() {
+    emit_error!(attr, "syntax error in {}", name);
+  }
+  Some(validator.map(|validator| format!("::fs_pro::shape::ContentValidator::{}", validator)))
+}
+
+// returns the type arguments of t if t is one of paths (e.g. Option<...>)
+fn type_args<'a>(t: &'a syn::TypePath, paths: &[&[&str]]) -> Option<Vec<&'a syn::Type>> {
+  let mut actual: Vec<String> = vec![];
+  for seg in &t.path.segments {
+    actual.push(seg.ident.to_string());
+  }
+  if !paths.iter().any(|path| vec_compare(&actual, path)) {
+    return None;
+  }
+  let mut types = vec![];
+  if let syn::PathArguments::AngleBracketed(args) = &t.path.segments.last().unwrap().arguments {
+    for arg in &args.args {
+      if let syn::GenericArgument::Type(inner) = arg {
+        types.push(inner);
+      }
+    }
+  }
+  Some(types)
+}
+
+fn is_string(t: &syn::Type) -> bool {
+  if let syn::Type::Path(t) = t {
+    if let Some(args) = type_args(t, &[&["String"], &["std", "string", "String"]]) {
+      return args.is_empty();
+    }
+  }
+  false
+}
+
+fn to_string(result: ParseResult, struct_name: String, rest: Option<String>) -> String {
   let mut shape_describe = String::from("[");
   let mut shape_new_top = String::new();
   let mut shape_new_bottom = String::new();
   let mut shape_new_file = String::new();
   let mut shape_new_shaped_dir = String::new();
   let mut shape_new_dir = String::new();
+  let mut shape_new_shaped_dirs = String::new();
+  let mut root_field = None;
   let mut i: usize = 0;
-  for (filed_name, filed_type, file_name, pattern, target_struct_name) in &result {
+  for (filed_name, filed_type, file_name, pattern, target_struct_name, options) in &result {
+    // the #[root] field isn't an entry, it's set from the root of the instance
+    if *filed_type == 5 {
+      shape_new_top
+        .push_str(format!("let mut {}: Option<::fs_pro::Dir> = None;\n", filed_name).as_str());
+      shape_new_bottom
+        .push_str(format!("{0}: {0}.ok_or_else(|| missing({0:?}))?,\n", filed_name).as_str());
+      root_field = Some(filed_name.clone());
+      continue;
+    }
     if *filed_type == 0 {
       shape_describe.push_str(
         format!(
-          "File(\"{}\", \"{}\")",
+          "File(\"{}\", {:?}, {})",
           filed_name,
-          file_name.as_ref().unwrap_or(&filed_name)
+          file_name.as_ref().unwrap_or(filed_name),
+          options.render()
         )
         .as_str(),
       );
@@ -83,10 +259,11 @@ fn to_string(result: ParseResult, struct_name: String) -> String {
     if *filed_type == 1 {
       shape_describe.push_str(
         format!(
-          "DirectoryPattern(\"{}\", \"{}\", \"{}\")",
+          "DirectoryPattern(\"{}\", {:?}, {:?}, {})",
           filed_name,
           file_name.as_ref().unwrap_or(&filed_name),
-          pattern.as_ref().unwrap()
+          pattern.as_ref().unwrap(),
+          options.render()
         )
         .as_str(),
       );
@@ -98,10 +275,11 @@ fn to_string(result: ParseResult, struct_name: String) -> String {
       let struct_path = join(target_struct_name.as_ref().unwrap(), "::");
       shape_describe.push_str(
         format!(
-          "DirectorySchema(\"{}\", \"{}\", {}::shape_describe())",
+          "DirectorySchema(\"{}\", {:?}, {}::shape_describe(), {})",
           filed_name,
           file_name.as_ref().unwrap_or(&filed_name),
-          struct_path
+          struct_path,
+          options.render()
         )
         .as_str(),
       );
@@ -109,15 +287,93 @@ fn to_string(result: ParseResult, struct_name: String) -> String {
         .push_str(format!("let mut {}: Option<{}> = None;\n", filed_name, struct_path).as_str());
       shape_new_shaped_dir.push_str(
         format!(
-          "{} => {} = Some({}::shape_new(dir)),\n",
+          "{} => {} = Some({}::shape_new(dir)?),\n",
           i, filed_name, struct_path
         )
         .as_str(),
       );
     }
+    if *filed_type == 3 || *filed_type == 4 {
+      let struct_path = join(target_struct_name.as_ref().unwrap(), "::");
+      shape_describe.push_str(
+        format!(
+          "DirectorySchemaPattern(\"{}\", {:?}, {}::shape_describe(), {})",
+          filed_name,
+          pattern.as_ref().unwrap(),
+          struct_path,
+          options.render()
+        )
+        .as_str(),
+      );
+      if *filed_type == 3 {
+        shape_new_top.push_str(
+          format!(
+            "let mut {}: Option<Vec<{}>> = None;\n",
+            filed_name, struct_path
+          )
+          .as_str(),
+        );
+        shape_new_shaped_dirs.push_str(
+          format!(
+            "{} => {} = Some(dirs.into_iter().map(|(_, dir)| {}::shape_new(dir)).collect::<Result<_, _>>()?),\n",
+            i, filed_name, struct_path
+          )
+          .as_str(),
+        );
+      } else {
+        shape_new_top.push_str(
+          format!(
+            "let mut {}: Option<::std::collections::HashMap<String, {}>> = None;\n",
+            filed_name, struct_path
+          )
+          .as_str(),
+        );
+        shape_new_shaped_dirs.push_str(
+          format!(
+            "{} => {} = Some(dirs.into_iter().map(|(name, dir)| Ok((name, {}::shape_new(dir)?))).collect::<Result<_, ::fs_pro::error::Error>>()?),\n",
+            i, filed_name, struct_path
+          )
+          .as_str(),
+        );
+      }
+    }
     i += 1;
     shape_describe.push_str(",");
-    shape_new_bottom.push_str(format!("{0}: {0}.unwrap(),\n", filed_name).as_str());
+    if options.optional {
+      shape_new_bottom.push_str(format!("{0}: {0},\n", filed_name).as_str());
+    } else {
+      shape_new_bottom
+        .push_str(format!("{0}: {0}.ok_or_else(|| missing({0:?}))?,\n", filed_name).as_str());
+    }
+  }
+  // only a struct that keeps it's root can be copied, moved or deleted as a whole
+  let shape_instance = match &root_field {
+    Some(filed_name) => format!(
+      r#"
+  impl ::fs_pro::shape::ShapeInstance for {} {{
+    fn root(&self) -> ::fs_pro::Dir {{
+       self.{}.clone()
+    }}
+  }}
+  "#,
+      struct_name, filed_name
+    ),
+    None => String::new(),
+  };
+  let root_arm = match &root_field {
+    Some(filed_name) => format!(
+      "ShapeInstItem::Root(dir) => {{ {} = Some(dir); continue; }}",
+      filed_name
+    ),
+    None => "ShapeInstItem::Root(_) => continue,".to_string(),
+  };
+  let mut shape_len = result.len();
+  if root_field.is_some() {
+    shape_len -= 1;
+  }
+  if let Some(rest) = rest {
+    shape_describe.push_str(format!("Rest({}),", rest).as_str());
+    shape_len += 1;
   }
   shape_describe.push_str("]");
   format!(
@@ -133,9 +389,16 @@ fn to_string(result: ParseResult, struct_name: String) -> String {
        }}
        &*shape
     }}
-    fn shape_new(inst: ::fs_pro::shape::ShapeInst) -> Self {{
+    fn shape_new(inst: ::fs_pro::shape::ShapeInst) -> ::fs_pro::error::Result<Self> {{
        use ::fs_pro::shape::ShapeInstItem;
        use ::core::option::Option;
+       #[allow(dead_code)]
+       fn missing(field: &str) -> ::fs_pro::error::Error {{
+         ::fs_pro::error::Error::new2(
+           ::fs_pro::error::ErrorKind::InvalidFolder,
+           format!("the shape instance has no entry for {{}}", field),
+         )
+       }}
        {}
        let mut i: usize = 0;
        for item in inst {{
@@ -152,35 +415,211 @@ fn to_string(result: ParseResult, struct_name: String) -> String {
               {}
               _ => {{}}
             }}
+            ShapeInstItem::ShapedDirectories(dirs) => match i {{
+              {}
+              _ => {{}}
+            }}
+            ShapeInstItem::Missing | ShapeInstItem::Variant(_, _) => {{}}
+            {}
           }}
           i += 1;
        }}
-       Self {{
+       Ok(Self {{
           {}
-       }}
+       }})
     }}
   }}
-  "#,
+  {}"#,
     struct_name,
-    result.len(),
+    shape_len,
     shape_describe,
     shape_new_top,
     shape_new_file,
     shape_new_dir,
     shape_new_shaped_dir,
-    shape_new_bottom
+    shape_new_shaped_dirs,
+    root_arm,
+    shape_new_bottom,
+    shape_instance
+  )
+}
+
+// the ShapeDescribe impl of an enum, every variant holds a shape and the folder
+// must match one of them, with #[instance] the enum implements ShapeInstance too
+fn enum_to_string(data: &syn::DataEnum, enum_name: String, instance: bool) -> String {
+  let mut variants = String::from("[");
+  let mut shape_new = String::new();
+  let mut root = String::new();
+  for (i, variant) in data.variants.iter().enumerate() {
+    let mut struct_path = None;
+    if let syn::Fields::Unnamed(fields) = &variant.fields {
+      if let (1, Some(syn::Type::Path(t))) =
+        (fields.unnamed.len(), fields.unnamed.first().map(|f| &f.ty))
+      {
+        let mut actual: Vec<String> = vec![];
+        for seg in &t.path.segments {
+          match seg.arguments {
+            syn::PathArguments::None => {}
+            _ => emit_error!(t, "types cannot have arguments"),
+          }
+          actual.push(seg.ident.to_string());
+        }
+        struct_path = Some(join(&actual, "::"));
+      }
+    }
+    let struct_path = match struct_path {
+      Some(struct_path) => struct_path,
+      None => {
+        emit_error!(
+          variant,
+          "variants must hold a single shape, like Variant(MyShapedDir)"
+        );
+        continue;
+      }
+    };
+    variants.push_str(
+      format!(
+        "({:?}, {}::shape_describe()),",
+        variant.ident.to_string(),
+        struct_path
+      )
+      .as_str(),
+    );
+    shape_new.push_str(
+      format!(
+        "Some(ShapeInstItem::Variant({}, inst)) => Ok({}::{}({}::shape_new(inst)?)),\n",
+        i, enum_name, variant.ident, struct_path
+      )
+      .as_str(),
+    );
+    root.push_str(
+      format!(
+        "{}::{}(inst) => ShapeInstance::root(inst),\n",
+        enum_name, variant.ident
+      )
+      .as_str(),
+    );
+  }
+  variants.push(']');
+  // every variant must hold a shape with a #[root] field, the compiler reports
+  // the ones that don't at the derive
+  let shape_instance = if instance {
+    format!(
+      r#"
+  impl ::fs_pro::shape::ShapeInstance for {} {{
+    fn root(&self) -> ::fs_pro::Dir {{
+       use ::fs_pro::shape::ShapeInstance;
+       match self {{
+         {}
+       }}
+    }}
+  }}"#,
+      enum_name, root
+    )
+  } else {
+    String::new()
+  };
+  format!(
+    r#"
+  #[doc(hidden)]
+  impl ::fs_pro::shape::ShapeDescribe for {} {{
+    fn shape_describe() -> &'static ::fs_pro::shape::ShapeSchemaStatic<'static> {{
+       use ::fs_pro::shape::ShapeDescribe;
+       use ::lazy_static::lazy_static;
+       lazy_static! {{
+         static ref variants: [(&'static str, &'static ::fs_pro::shape::ShapeSchemaStatic<'static>); {}] = {};
+         static ref shape: [::fs_pro::shape::ShapeItemStatic<'static>; 1] =
+           [::fs_pro::shape::ShapeItemStatic::Variants(&*variants)];
+       }}
+       &*shape
+    }}
+    fn shape_new(inst: ::fs_pro::shape::ShapeInst) -> ::fs_pro::error::Result<Self> {{
+       use ::fs_pro::shape::{{ShapeDescribe, ShapeInstItem}};
+       match inst
+         .into_iter()
+         .find(|item| matches!(item, ShapeInstItem::Variant(_, _)))
+       {{
+         {}
+         _ => Err(::fs_pro::error::Error::new(
+           ::fs_pro::error::ErrorKind::InvalidFolder,
+           "the folder matches no variant",
+         )),
+       }}
+    }}
+  }}
+  {}
+  "#,
+    enum_name,
+    data.variants.len(),
+    variants,
+    shape_new,
+    shape_instance
   )
 }
 
-#[proc_macro_derive(Shape, attributes(name, pattern))]
+#[proc_macro_derive(
+  Shape,
+  attributes(
+    name,
+    pattern,
+    rest,
+    rest_pattern,
+    content,
+    content_file,
+    max_size,
+    min_size,
+    non_empty,
+    utf8,
+    json,
+    validate_with,
+    mode,
+    executable,
+    readonly,
+    root,
+    instance
+  )
+)]
 #[proc_macro_error]
 pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
   let input = parse_macro_input!(item as syn::DeriveInput);
-  let regex = regex::Regex::new(" *= *\"([^/\"\\\\]*)\"").unwrap();
   let mut result: ParseResult = vec![];
+  let mut rest = None;
+  let mut instance = false;
   #[allow(unused_assignments)]
   let mut struct_name = String::new();
+  for attr in &input.attrs {
+    if attr.path.is_ident("instance") {
+      match attr.parse_meta() {
+        Ok(syn::Meta::Path(_)) => instance = true,
+        _ => emit_error!(attr, "syntax error in instance"),
+      }
+    } else if attr.path.is_ident("rest") {
+      match str_attr(attr).as_deref() {
+        Some("allow") => rest = Some("::fs_pro::shape::ShapeRest::Allow".to_string()),
+        Some("forbid") => rest = Some("::fs_pro::shape::ShapeRest::Forbid".to_string()),
+        _ => emit_error!(attr, "rest can only be \"allow\" or \"forbid\""),
+      }
+    } else if attr.path.is_ident("rest_pattern") {
+      let pattern = match str_attr(attr) {
+        Some(pattern) => pattern,
+        None => abort!(attr, "syntax error in rest_pattern"),
+      };
+      if !valid_pattern(&pattern) {
+        emit_error!(attr, "invalid pattern {:?}", pattern)
+      }
+      rest = Some(format!(
+        "::fs_pro::shape::ShapeRest::Pattern(::std::borrow::Cow::Borrowed({:?}))",
+        pattern
+      ));
+    }
+  }
   if let syn::Data::Struct(strc) = &input.data {
+    if instance {
+      emit_error!(
+        input,
+        "instance can only be used on enums, add a #[root] field to the struct instead"
+      );
+    }
     struct_name = input.ident.to_string();
     if let syn::Fields::Named(fileds) = &strc.fields {
       for item in &fileds.named {
@@ -193,7 +632,37 @@ pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
         let mut pattern = None;
         let mut target_struct_name = None;
         let mut filed_type: usize = 0;
-        if let syn::Type::Path(t) = &item.ty {
+        let mut is_root = false;
+        let mut options = ItemOptions::default();
+        let mut ty = &item.ty;
+        if let syn::Type::Path(t) = ty {
+          let option_paths: &[&[&str]] = &[
+            &["Option"],
+            &["std", "option", "Option"],
+            &["core", "option", "Option"],
+          ];
+          let vec_paths: &[&[&str]] = &[&["Vec"], &["std", "vec", "Vec"]];
+          let hash_map_paths: &[&[&str]] = &[&["HashMap"], &["std", "collections", "HashMap"]];
+          if let Some(args) = type_args(t, option_paths) {
+            if args.len() == 1 {
+              options.optional = true;
+              ty = args[0];
+            }
+          } else if let Some(args) = type_args(t, vec_paths) {
+            if args.len() == 1 {
+              filed_type = 3;
+              ty = args[0];
+            }
+          } else if let Some(args) = type_args(t, hash_map_paths) {
+            if args.len() == 2 && is_string(args[0]) {
+              filed_type = 4;
+              ty = args[1];
+            } else {
+              emit_error!(t, "HashMap fields must have String keys");
+            }
+          }
+        }
+        if let syn::Type::Path(t) = ty {
           let mut actual: Vec<String> = vec![];
           for seg in &t.path.segments {
             match seg.arguments {
@@ -202,9 +671,22 @@ pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
             }
             actual.push(seg.ident.to_string());
           }
-          if vec_compare2(&actual, &["File"], &["fs_pro", "File"]) {
+          let is_file = vec_compare2(&actual, &["File"], &["fs_pro", "File"]);
+          let is_dir = vec_compare2(&actual, &["Dir"], &["fs_pro", "Dir"]);
+          if filed_type == 3 || filed_type == 4 {
+            if options.optional {
+              emit_error!(item.ty, "Vec and HashMap fields cannot be optional");
+            }
+            if is_file || is_dir {
+              emit_error!(
+                t,
+                "Vec and HashMap fields can only hold structs implementing Shape"
+              );
+            }
+            target_struct_name = Some(actual);
+          } else if is_file {
             filed_type = 0;
-          } else if vec_compare2(&actual, &["Dir"], &["fs_pro", "Dir"]) {
+          } else if is_dir {
             filed_type = 1;
           } else {
             target_struct_name = Some(actual);
@@ -213,7 +695,7 @@ pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
         } else {
           emit_error!(
             item.ty,
-            "a field can be only File, Dir or a struct implementing Shape"
+            "a field can be only File, Dir, a struct implementing Shape or an Option of them"
           );
         }
         for attr in &item.attrs {
@@ -221,38 +703,96 @@ pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
             unimplemented!()
           }
           if attr.path.is_ident("name") {
-            let st = attr.tokens.to_string();
-            if !regex.is_match(st.as_str()) {
-              emit_error!(attr, "syntax error in name");
+            if filed_type == 3 || filed_type == 4 {
+              emit_error!(
+                attr,
+                "name cannot be used with Vec or HashMap fields, use pattern"
+              );
+            }
+            match str_attr(attr) {
+              Some(name) => {
+                if let Err(message) = valid_name(&name) {
+                  emit_error!(attr, message);
+                }
+                file_name = Some(name);
+              }
+              None => emit_error!(attr, "syntax error in name"),
             }
-            file_name = Some(
-              regex
-                .captures(st.as_str())
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str()
-                .to_string(),
-            );
           } else if attr.path.is_ident("pattern") {
-            if filed_type != 1 {
-              emit_error!(attr, "pattern can only be used with Dir fields");
+            if filed_type != 1 && filed_type != 3 && filed_type != 4 {
+              emit_error!(
+                attr,
+                "pattern can only be used with Dir, Vec or HashMap fields"
+              );
             }
-            let st = attr.tokens.to_string();
-            if !regex.is_match(st.as_str()) {
-              emit_error!(attr, "syntax error in pattern");
+            pattern = str_attr(attr);
+            if pattern.is_none() {
+              abort!(attr, "syntax error in pattern");
             }
-            pattern = Some(
-              regex
-                .captures(st.as_str())
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str()
-                .to_string(),
-            );
-            if !valid_pattern((&pattern).as_ref().unwrap()) {
-              emit_error!(attr, "invlaid pattern")
+            if pattern.as_ref().unwrap().contains('/') {
+              emit_error!(attr, "pattern cannot contain '/'");
+            } else if !valid_pattern(pattern.as_ref().unwrap()) {
+              emit_error!(attr, "invalid pattern {:?}", pattern.as_ref().unwrap())
+            }
+          } else if attr.path.is_ident("content") || attr.path.is_ident("content_file") {
+            if filed_type != 0 {
+              emit_error!(attr, "content can only be used with File fields");
+            }
+            if options.content.is_some() {
+              emit_error!(
+                attr,
+                "a field can only have one content or content_file attribute"
+              );
+            }
+            match attr.parse_meta() {
+              Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(lit),
+                ..
+              })) => {
+                if attr.path.is_ident("content") {
+                  options.content = Some(format!("{:?}.as_bytes()", lit.value()));
+                } else {
+                  options.content = Some(format!("&include_bytes!({:?})[..]", lit.value()));
+                }
+              }
+              _ => emit_error!(attr, "syntax error in content"),
+            }
+          } else if attr.path.is_ident("mode")
+            || attr.path.is_ident("executable")
+            || attr.path.is_ident("readonly")
+          {
+            if filed_type == 3 || filed_type == 4 {
+              emit_error!(attr, "modes cannot be used with Vec or HashMap fields");
+            }
+            match attr.parse_meta() {
+              Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Int(lit),
+                ..
+              }))
+                if attr.path.is_ident("mode") =>
+              {
+                match lit.base10_parse::<u32>() {
+                  Ok(mode) if mode <= 0o7777 => options.mode = Some(mode),
+                  _ => emit_error!(attr, "invalid mode"),
+                }
+              }
+              Ok(syn::Meta::Path(_)) if attr.path.is_ident("executable") => {
+                options.executable = true
+              }
+              Ok(syn::Meta::Path(_)) if attr.path.is_ident("readonly") => options.readonly = true,
+              _ => emit_error!(attr, "syntax error in mode"),
+            }
+          } else if attr.path.is_ident("root") {
+            match attr.parse_meta() {
+              Ok(syn::Meta::Path(_)) => is_root = true,
+              _ => emit_error!(attr, "syntax error in root"),
+            }
+          } else if let Some(validator) = validator_attr(attr) {
+            if filed_type != 0 {
+              emit_error!(attr, "content checks can only be used with File fields");
+            }
+            if let Some(validator) = validator {
+              options.validators.push(validator);
             }
           } else {
             if !attr.path.is_ident("doc") {
@@ -260,26 +800,55 @@ pub fn derive_helper_attr(item: TokenStream) -> TokenStream {
             }
           }
         }
+        if is_root {
+          if filed_type != 1 || options.optional {
+            emit_error!(item, "the root field must be a Dir");
+          } else if pattern.is_some() || file_name.is_some() {
+            emit_error!(item, "the root field cannot have a name or a pattern");
+          } else if options.mode.is_some() || options.executable || options.readonly {
+            emit_error!(item, "the root field cannot have a mode");
+          }
+          filed_type = 5;
+        }
         if filed_type == 1 {
           if let None = pattern {
             emit_error!(item, "Dir fields must have pattern attribute");
           }
         }
+        if filed_type == 3 || filed_type == 4 {
+          if pattern.is_none() {
+            emit_error!(item, "Vec and HashMap fields must have pattern attribute");
+          }
+        }
         result.push((
           filed_name,
           filed_type,
           file_name,
           pattern,
           target_struct_name,
+          options,
         ));
       }
     } else {
       abort!(input, "fields must be named")
     }
+  } else if let syn::Data::Enum(data) = &input.data {
+    if rest.is_some() {
+      emit_error!(
+        input,
+        "rest can only be used on structs, set it on the variants"
+      );
+    }
+    if data.variants.is_empty() {
+      abort!(input, "enums must have at least one variant");
+    }
+    return enum_to_string(data, input.ident.to_string(), instance)
+      .parse::<TokenStream>()
+      .unwrap();
   } else {
-    abort!(input, "Shape can be only used on struct(s)")
+    abort!(input, "Shape can be only used on structs and enums")
   }
-  to_string(result, struct_name)
+  to_string(result, struct_name, rest)
     .parse::<TokenStream>()
     .unwrap()
 }
@@ -0,0 +1,22 @@
+//! the glob rules of fs_pro shape patterns, fs_pro uses them to match names and
+//! fs_pro_macros to check patterns at compile time, so both accept the same patterns
+
+#![deny(missing_docs)]
+
+use globset::{Glob, GlobBuilder};
+
+pub use globset::Error;
+
+/// compiles a pattern, returning whether it's negated by a leading `!`
+/// and the glob that must match the whole file name
+pub fn compile(pattern: &str) -> Result<(bool, Glob), Error> {
+  let (negated, glob) = match pattern.strip_prefix('!') {
+    Some(rest) => (true, rest),
+    None => (false, pattern),
+  };
+  let glob = GlobBuilder::new(glob)
+    .literal_separator(true)
+    .backslash_escape(true)
+    .build()?;
+  Ok((negated, glob))
+}
@@ -1,12 +1,16 @@
 use crate::error;
 use crate::file;
 use crate::file::File;
+#[cfg(feature = "hash")]
+use crate::hash;
+use crate::lock;
 use crate::path_stuff;
 use fs_extra;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// the Dir struct is a struct for helping you
 /// working with directories
@@ -503,6 +507,92 @@ impl Dir {
     }
     Ok(result)
   }
+  /// locks the folder by creating a lockfile (`.lock`) in it, blocks until no
+  /// other process holds the lock
+  ///
+  /// the lockfile contains the pid of the process, the time it was created and the
+  /// number of the lock in the process, a lockfile left by a process that exited is
+  /// removed (on linux, elsewhere after an hour), the lockfile is removed when the
+  /// returned lock is dropped
+  ///
+  /// the lock isn't reentrant, locking a folder the process (or thread) already
+  /// locked blocks forever, use `lock_timeout` or `try_lock` if that can happen
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// # let dir = fs_pro::Dir::new("state")?;
+  /// let lock = dir.lock()?;
+  /// dir.create_file("state.json")?.write("{}")?;
+  /// lock.unlock()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn lock(&self) -> error::Result<lock::DirLock> {
+    lock::lock_dir(&self.path)
+  }
+  /// like `lock` but returns None instead of blocking
+  pub fn try_lock(&self) -> error::Result<Option<lock::DirLock>> {
+    lock::try_lock_dir(&self.path)
+  }
+  /// like `lock` but returns None if the lock isn't taken within timeout
+  pub fn lock_timeout(&self, timeout: Duration) -> error::Result<Option<lock::DirLock>> {
+    lock::lock_dir_timeout(&self.path, timeout)
+  }
+  /// writes a checksum manifest of every file in the folder (recursively) in the
+  /// format of `sha256sum` (`<checksum>  <path>` per line), path is relative to
+  /// the folder and the manifest itself isn't listed
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::hash::Algorithm;
+  ///
+  /// # let dir = fs_pro::Dir::new("release")?;
+  /// dir.write_manifest("SHA256SUMS", Algorithm::Sha256)?;
+  /// // now `sha256sum -c SHA256SUMS` works in dir
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "hash")]
+  pub fn write_manifest<P: AsRef<Path>>(
+    &self,
+    path: P,
+    algorithm: hash::Algorithm,
+  ) -> error::Result<File> {
+    let manifest = self.path.join(path);
+    hash::write_manifest(&self.path, &manifest, algorithm)?;
+    File::new(manifest)
+  }
+  /// checks the files of the folder against a manifest written by `write_manifest`
+  /// or `sha256sum` (path is relative to the folder)
+  ///
+  /// the algorithm is found from the length of the checksums (SHA-256, SHA-1 or
+  /// CRC32), use `verify_manifest_with` for BLAKE3 manifests
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// # let dir = fs_pro::Dir::new("release")?;
+  /// let report = dir.verify_manifest("SHA256SUMS")?;
+  /// if !report.is_valid() {
+  ///   println!("missing: {:?}", report.missing);
+  ///   println!("extra: {:?}", report.extra);
+  ///   println!("mismatched: {:?}", report.mismatched);
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  /// # Errors
+  /// - InvalidFile if a line of the manifest is invalid
+  /// - the manifest or a file can't be read
+  #[cfg(feature = "hash")]
+  pub fn verify_manifest<P: AsRef<Path>>(&self, path: P) -> error::Result<hash::ManifestReport> {
+    hash::verify_manifest(&self.path, &self.path.join(path), None)
+  }
+  /// like `verify_manifest` but with the algorithm of the manifest given
+  #[cfg(feature = "hash")]
+  pub fn verify_manifest_with<P: AsRef<Path>>(
+    &self,
+    path: P,
+    algorithm: hash::Algorithm,
+  ) -> error::Result<hash::ManifestReport> {
+    hash::verify_manifest(&self.path, &self.path.join(path), Some(algorithm))
+  }
   /// checks if an entry (file or folder) exists in the dir
   pub fn entry_exists<P: AsRef<Path>>(&self, path: P) -> bool {
     self.path.join(path).exists()
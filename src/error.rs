@@ -7,7 +7,10 @@ use std::io::ErrorKind as IoErrorKind;
 use std::path::PathBuf;
 
 /// A list specifying general categories of fs_pro error.
+///
+/// new kinds can be added in minor versions, so matching on it needs a wildcard arm
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
   /// An entity was not found.
   NotFound,
@@ -33,9 +36,21 @@ pub enum ErrorKind {
   PathNoFilenameFound,
   /// on extension found in path
   PathNoExtensionFound,
+  /// a shape pattern is not a valid glob
+  InvalidPattern,
   #[cfg(feature = "json")]
   /// an error happen read file as json
   JsonError(serde_json::error::Error),
+  #[cfg(feature = "yaml")]
+  /// an error happen reading or writing yaml
+  YamlError(serde_yaml::Error),
+  #[cfg(feature = "toml")]
+  /// an error happen reading or writing toml (a `toml::de::Error` or `toml::ser::Error`)
+  TomlError(Box<dyn StdError + Send + Sync>),
+  /// a shape schema file is invalid
+  InvalidSchema,
+  /// the checksum of a file isn't the expected one
+  ChecksumMismatch,
   /// any other error
   Other,
 }
@@ -57,8 +72,15 @@ impl ErrorKind {
       ErrorKind::PathNoParentFound => "cannot find any parent directory",
       ErrorKind::PathNoFilenameFound => "cannot find filename",
       ErrorKind::PathNoExtensionFound => "cannot find file extension",
+      ErrorKind::InvalidPattern => "invalid pattern",
       #[cfg(feature = "json")]
       ErrorKind::JsonError(_) => "an error happen reading file as json",
+      #[cfg(feature = "yaml")]
+      ErrorKind::YamlError(_) => "an error happen reading or writing yaml",
+      #[cfg(feature = "toml")]
+      ErrorKind::TomlError(_) => "an error happen reading or writing toml",
+      ErrorKind::InvalidSchema => "invalid shape schema",
+      ErrorKind::ChecksumMismatch => "checksum mismatch",
     }
   }
 }
@@ -1,12 +1,63 @@
 use crate::error;
+#[cfg(feature = "hash")]
+use crate::hash;
+use crate::lock;
 use crate::path_stuff;
 use fs_extra;
 // use serde;
 // use serde_json;
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 
+/// an iterator over the lines of a file, see `File::lines`
+#[derive(Debug)]
+pub struct Lines {
+  lines: std::io::Lines<BufReader<fs::File>>,
+  path: PathBuf,
+}
+
+impl Iterator for Lines {
+  type Item = error::Result<String>;
+  fn next(&mut self) -> Option<Self::Item> {
+    let line = self.lines.next()?;
+    Some(error::result_from_io(line).map_err(|e| e.set_path(self.path.clone())))
+  }
+}
+
+/// an iterator over the content of a file in chunks, see `File::chunks`
+#[derive(Debug)]
+pub struct Chunks {
+  reader: BufReader<fs::File>,
+  size: usize,
+  path: PathBuf,
+  done: bool,
+}
+
+impl Iterator for Chunks {
+  type Item = error::Result<Vec<u8>>;
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let mut chunk = Vec::with_capacity(self.size);
+    let res = (&mut self.reader)
+      .take(self.size as u64)
+      .read_to_end(&mut chunk);
+    match error::result_from_io(res) {
+      Ok(0) => {
+        self.done = true;
+        None
+      }
+      Ok(_) => Some(Ok(chunk)),
+      Err(e) => {
+        self.done = true;
+        Some(Err(e.set_path(self.path.clone())))
+      }
+    }
+  }
+}
+
 /// the File struct is a struct to help you work with files
 #[derive(Debug, Clone)]
 pub struct File {
@@ -162,13 +213,113 @@ impl File {
     Ok(())
   }
   /// writes to file
+  ///
+  /// with the `atomic_write` feature it's the same as `write_atomic`
   /// ```
   /// file.write("hi");
   /// file.write(vec![10, 100, 100]);
   /// ```
   pub fn write<C: AsRef<[u8]>>(&self, content: C) -> error::Result<()> {
+    if cfg!(feature = "atomic_write") {
+      return self.write_atomic(content);
+    }
     error::result_from_io(fs::write(self.path.as_path(), content))
   }
+  /// writes to file so a reader sees either the old or the new content, never a part of it
+  ///
+  /// the content is written to a temp file next to the file which is synced to disk and
+  /// renamed over the file, the permissions of the file are kept (and the folder is
+  /// synced after the rename on unix), if the file is a symlink the file it points to
+  /// is replaced
+  ///
+  /// locks held on the file don't hold on the new one, see `lock_exclusive`
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// # let file = fs_pro::File::new("config.toml")?;
+  /// file.write_atomic("key = \"value\"")?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn write_atomic<C: AsRef<[u8]>>(&self, content: C) -> error::Result<()> {
+    let set_path = |e: error::Error| e.set_path(self.path.clone());
+    let target = match fs::symlink_metadata(&self.path) {
+      Ok(metadata) if metadata.file_type().is_symlink() => {
+        error::result_from_io(fs::canonicalize(&self.path)).map_err(set_path)?
+      }
+      _ => self.path.clone(),
+    };
+    let parent = match target.parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+      _ => PathBuf::from("."),
+    };
+    let name =
+      error::result_from_option2(target.file_name(), error::ErrorKind::PathNoFilenameFound)?;
+    let temp = parent.join(format!(
+      ".{}.tmp-{}-{}",
+      name.to_string_lossy(),
+      std::process::id(),
+      path_stuff::get_rand_chars(6)
+    ));
+    let write = || -> error::Result<()> {
+      let mut file = error::result_from_io(
+        fs::OpenOptions::new()
+          .write(true)
+          .create_new(true)
+          .open(&temp),
+      )?;
+      error::result_from_io(file.write_all(content.as_ref()))?;
+      if let Ok(metadata) = fs::metadata(&target) {
+        error::result_from_io(fs::set_permissions(&temp, metadata.permissions()))?;
+      }
+      error::result_from_io(file.sync_all())?;
+      error::result_from_io(fs::rename(&temp, &target))
+    };
+    if let Err(e) = write() {
+      let _ = fs::remove_file(&temp);
+      return Err(set_path(e));
+    }
+    // the rename is only durable once the folder is synced
+    #[cfg(unix)]
+    error::result_from_io(fs::File::open(&parent).and_then(|dir| dir.sync_all()))
+      .map_err(set_path)?;
+    Ok(())
+  }
+  /// locks the file for writing, blocks until no other lock is held on it,
+  /// the file is created if it doesn't exist
+  ///
+  /// the lock is advisory (only other locks respect it) and released when the
+  /// returned lock is dropped
+  ///
+  /// the lock is held on the file that is open, `write_atomic` (and `write` with the
+  /// `atomic_write` feature) replaces it with a new file that isn't locked, so a
+  /// locked file must be written through `FileLock::file` or a separate lockfile
+  /// must be locked instead
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// # let file = fs_pro::File::new("counter.txt")?;
+  /// let lock = fs_pro::File::new("counter.txt.lock")?.lock_exclusive()?;
+  /// let count: u32 = file.read_to_string()?.trim().parse().unwrap_or(0);
+  /// file.write((count + 1).to_string())?;
+  /// drop(lock);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn lock_exclusive(&self) -> error::Result<lock::FileLock> {
+    lock::lock_file(&self.path, true)
+  }
+  /// locks the file for reading, blocks while an exclusive lock is held on it,
+  /// any number of shared locks can be held at once
+  pub fn lock_shared(&self) -> error::Result<lock::FileLock> {
+    lock::lock_file(&self.path, false)
+  }
+  /// like `lock_exclusive` but returns None instead of blocking
+  pub fn try_lock_exclusive(&self) -> error::Result<Option<lock::FileLock>> {
+    lock::try_lock_file(&self.path, true)
+  }
+  /// like `lock_shared` but returns None instead of blocking
+  pub fn try_lock_shared(&self) -> error::Result<Option<lock::FileLock>> {
+    lock::try_lock_file(&self.path, false)
+  }
   /// reads the file as Vec<u8>
   /// ```
   /// file.read() // => [10, 124, ...]
@@ -183,6 +334,90 @@ impl File {
   pub fn read_to_string(&self) -> error::Result<String> {
     error::result_from_io(fs::read_to_string(self.path.as_path()))
   }
+  /// opens the file for buffered reading
+  /// ```no_run
+  /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+  /// # let file = fs_pro::File::new("data.txt")?;
+  /// use std::io::Read;
+  ///
+  /// let mut header = [0; 4];
+  /// file.reader()?.read_exact(&mut header)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn reader(&self) -> error::Result<BufReader<fs::File>> {
+    let file = error::result_from_io(fs::File::open(&self.path))
+      .map_err(|e| e.set_path(self.path.clone()))?;
+    Ok(BufReader::new(file))
+  }
+  /// opens the file for buffered writing, the file is created or truncated
+  ///
+  /// the buffer is flushed when the writer is dropped but errors are ignored then,
+  /// call `flush` to get them
+  /// ```no_run
+  /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+  /// # let file = fs_pro::File::new("data.txt")?;
+  /// use std::io::Write;
+  ///
+  /// let mut writer = file.writer()?;
+  /// for i in 0..1000 {
+  ///   writeln!(writer, "line {}", i)?;
+  /// }
+  /// writer.flush()?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn writer(&self) -> error::Result<BufWriter<fs::File>> {
+    let file = error::result_from_io(fs::File::create(&self.path))
+      .map_err(|e| e.set_path(self.path.clone()))?;
+    Ok(BufWriter::new(file))
+  }
+  /// iterates over the lines of the file without reading all of it,
+  /// line endings (`\n` or `\r\n`) are removed
+  /// ```no_run
+  /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+  /// # let file = fs_pro::File::new("data.txt")?;
+  /// for line in file.lines()? {
+  ///   println!("{}", line?);
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn lines(&self) -> error::Result<Lines> {
+    Ok(Lines {
+      lines: self.reader()?.lines(),
+      path: self.path.clone(),
+    })
+  }
+  /// iterates over the content of the file in chunks of size bytes,
+  /// only the last chunk can be smaller
+  /// ```no_run
+  /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+  /// # let file = fs_pro::File::new("data.txt")?;
+  /// let mut size = 0;
+  /// for chunk in file.chunks(64 * 1024)? {
+  ///   size += chunk?.len();
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  /// # Errors
+  /// - size is 0
+  /// - the file can't be opened
+  pub fn chunks(&self, size: usize) -> error::Result<Chunks> {
+    if size == 0 {
+      return Err(error::Error::new(
+        error::ErrorKind::Other,
+        "the chunk size must be more than 0",
+      ));
+    }
+    Ok(Chunks {
+      reader: self.reader()?,
+      size,
+      path: self.path.clone(),
+      done: false,
+    })
+  }
   /// append to file
   /// NOTE: will create file if it doesn't exists
   /// ```
@@ -306,6 +541,88 @@ impl File {
     ))?;
     Ok(File::new(to)?)
   }
+  /// hashes the content of the file, it's read in chunks so big files aren't
+  /// loaded in memory
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::hash::Algorithm;
+  ///
+  /// # let file = fs_pro::File::new("hello.txt")?;
+  /// println!("{}", file.hash(Algorithm::Sha256)?); // => "2cf24dba5fb0a30e..."
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "hash")]
+  pub fn hash(&self, algorithm: hash::Algorithm) -> error::Result<hash::Checksum> {
+    hash::hash_file(&self.path, algorithm)
+  }
+  /// checks the file has the expected checksum (hashing it with the algorithm
+  /// of the checksum)
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::hash::{Algorithm, Checksum};
+  ///
+  /// # let file = fs_pro::File::new("hello.txt")?;
+  /// let expected = Checksum::from_hex(
+  ///   Algorithm::Sha256,
+  ///   "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+  /// )?;
+  /// if !file.verify(&expected)? {
+  ///   println!("{} is corrupted", file.path.display());
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(feature = "hash")]
+  pub fn verify(&self, expected: &hash::Checksum) -> error::Result<bool> {
+    Ok(self.hash(expected.algorithm())? == *expected)
+  }
+  /// copies the file to dest and checks the copy has the same checksum as the file,
+  /// the copy is deleted if it doesn't
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::hash::Algorithm;
+  ///
+  /// # let file = fs_pro::File::new("data.bin")?;
+  /// let copy = file.copy_verified("backup/data.bin", Algorithm::Blake3)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  /// # Errors
+  /// - ChecksumMismatch if the checksums don't match
+  /// - the errors of `File::copy`
+  #[cfg(feature = "hash")]
+  pub fn copy_verified<P: AsRef<Path>>(
+    &self,
+    destination: P,
+    algorithm: hash::Algorithm,
+  ) -> error::Result<File> {
+    let expected = self.hash(algorithm)?;
+    let copy = self.copy(destination)?;
+    match copy.hash(algorithm) {
+      Ok(checksum) if checksum == expected => Ok(copy),
+      Ok(checksum) => {
+        let _ = copy.delete();
+        Err(
+          error::Error::new2(
+            error::ErrorKind::ChecksumMismatch,
+            format!(
+              "the copy of {} has the {} checksum {} instead of {}",
+              self.path.display(),
+              algorithm,
+              checksum,
+              expected
+            ),
+          )
+          .set_path(copy.path),
+        )
+      }
+      Err(e) => {
+        let _ = copy.delete();
+        Err(e)
+      }
+    }
+  }
   /// parses file as json
   /// ```
   /// use serde_json::Value;
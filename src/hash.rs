@@ -0,0 +1,377 @@
+use crate::error::{self, Error, ErrorKind};
+use sha2::Digest;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// the size of the chunks read while hashing
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// a hash algorithm, see `File::hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+  /// SHA-256
+  Sha256,
+  /// SHA-1, only use it to check against existing checksums
+  Sha1,
+  /// BLAKE3 (with a 32 bytes output)
+  Blake3,
+  /// CRC-32 (IEEE), it's fast but only detects accidental changes
+  Crc32,
+}
+
+impl Algorithm {
+  /// the name of the algorithm like "sha256" (as used in the name of
+  /// `sha256sum` and `b3sum`)
+  pub fn name(&self) -> &'static str {
+    match self {
+      Algorithm::Sha256 => "sha256",
+      Algorithm::Sha1 => "sha1",
+      Algorithm::Blake3 => "blake3",
+      Algorithm::Crc32 => "crc32",
+    }
+  }
+  /// the length of a checksum in bytes
+  pub fn output_len(&self) -> usize {
+    match self {
+      Algorithm::Sha256 => 32,
+      Algorithm::Sha1 => 20,
+      Algorithm::Blake3 => 32,
+      Algorithm::Crc32 => 4,
+    }
+  }
+}
+
+impl fmt::Display for Algorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for Algorithm {
+  type Err = Error;
+  fn from_str(name: &str) -> error::Result<Algorithm> {
+    match name.to_ascii_lowercase().replace('-', "").as_str() {
+      "sha256" => Ok(Algorithm::Sha256),
+      "sha1" => Ok(Algorithm::Sha1),
+      "blake3" => Ok(Algorithm::Blake3),
+      "crc32" => Ok(Algorithm::Crc32),
+      _ => Err(Error::new2(
+        ErrorKind::Other,
+        format!("unknown hash algorithm \"{}\"", name),
+      )),
+    }
+  }
+}
+
+/// the checksum of a file, it's displayed as lowercase hex
+/// ```no_run
+/// # fn main() -> fs_pro::error::Result<()> {
+/// use fs_pro::hash::{Algorithm, Checksum};
+///
+/// # let file = fs_pro::File::new("hello.txt")?;
+/// let checksum = file.hash(Algorithm::Sha256)?;
+/// println!("{}", checksum); // => "2cf24dba5fb0a30e..."
+/// let expected = Checksum::from_hex(
+///   Algorithm::Sha256,
+///   "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+/// )?;
+/// assert!(file.verify(&expected)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Checksum {
+  algorithm: Algorithm,
+  bytes: Vec<u8>,
+}
+
+impl Checksum {
+  /// parses a hex checksum (upper or lower case)
+  pub fn from_hex(algorithm: Algorithm, hex: &str) -> error::Result<Checksum> {
+    let invalid = || {
+      Error::new2(
+        ErrorKind::Other,
+        format!("\"{}\" isn't a valid {} checksum", hex, algorithm),
+      )
+    };
+    if !hex.is_ascii() || hex.len() != algorithm.output_len() * 2 {
+      return Err(invalid());
+    }
+    let mut bytes = vec![];
+    for i in (0..hex.len()).step_by(2) {
+      bytes.push(u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid())?);
+    }
+    Ok(Checksum { algorithm, bytes })
+  }
+  /// the algorithm of the checksum
+  pub fn algorithm(&self) -> Algorithm {
+    self.algorithm
+  }
+  /// the checksum as bytes
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.bytes
+  }
+  /// the checksum as lowercase hex
+  pub fn to_hex(&self) -> String {
+    self
+      .bytes
+      .iter()
+      .map(|byte| format!("{:02x}", byte))
+      .collect()
+  }
+}
+
+impl fmt::Display for Checksum {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.to_hex())
+  }
+}
+
+enum Hasher {
+  Sha256(sha2::Sha256),
+  Sha1(sha1::Sha1),
+  Blake3(Box<blake3::Hasher>),
+  Crc32(crc32fast::Hasher),
+}
+
+impl Hasher {
+  fn new(algorithm: Algorithm) -> Hasher {
+    match algorithm {
+      Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+      Algorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+      Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+      Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+    }
+  }
+  fn update(&mut self, data: &[u8]) {
+    match self {
+      Hasher::Sha256(hasher) => hasher.update(data),
+      Hasher::Sha1(hasher) => hasher.update(data),
+      Hasher::Blake3(hasher) => {
+        hasher.update(data);
+      }
+      Hasher::Crc32(hasher) => hasher.update(data),
+    }
+  }
+  fn finish(self) -> Vec<u8> {
+    match self {
+      Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+      Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+      // big endian like `crc32` and `cksum -a crc32b` print it
+      Hasher::Crc32(hasher) => hasher.finalize().to_be_bytes().to_vec(),
+    }
+  }
+}
+
+// hashes the file at path reading it in chunks
+pub(crate) fn hash_file(path: &Path, algorithm: Algorithm) -> error::Result<Checksum> {
+  let set_path = |e: Error| e.set_path(path.to_path_buf());
+  let mut file = error::result_from_io(std::fs::File::open(path)).map_err(set_path)?;
+  let mut hasher = Hasher::new(algorithm);
+  let mut buf = vec![0; CHUNK_SIZE];
+  loop {
+    let read = match file.read(&mut buf) {
+      Ok(0) => break,
+      Ok(read) => read,
+      Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(set_path(Error::from_io(e))),
+    };
+    hasher.update(&buf[..read]);
+  }
+  Ok(Checksum {
+    algorithm,
+    bytes: hasher.finish(),
+  })
+}
+
+/// the result of checking a folder against a checksum manifest, see `Dir::verify_manifest`,
+/// the paths are relative to the folder
+/// ```no_run
+/// # fn main() -> fs_pro::error::Result<()> {
+/// # let dir = fs_pro::Dir::new("release")?;
+/// let report = dir.verify_manifest("SHA256SUMS")?;
+/// for path in &report.mismatched {
+///   println!("{} is corrupted", path.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ManifestReport {
+  /// files listed in the manifest that don't exist
+  pub missing: Vec<PathBuf>,
+  /// files that aren't listed in the manifest
+  pub extra: Vec<PathBuf>,
+  /// files that don't have the checksum listed in the manifest
+  pub mismatched: Vec<PathBuf>,
+}
+
+impl ManifestReport {
+  /// returns true if the folder matches the manifest exactly
+  pub fn is_valid(&self) -> bool {
+    self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+  }
+}
+
+// the files of dir relative to it (with '/' as separator) sorted, symlinks to
+// folders aren't followed
+fn files_in(root: &Path, relative: &Path, files: &mut Vec<String>) -> error::Result<()> {
+  let dir = root.join(relative);
+  let entries = error::result_from_io(fs::read_dir(&dir)).map_err(|e| e.set_path(dir.clone()))?;
+  for entry in entries {
+    let entry = error::result_from_io(entry).map_err(|e| e.set_path(dir.clone()))?;
+    let path = relative.join(entry.file_name());
+    let file_type =
+      error::result_from_io(entry.file_type()).map_err(|e| e.set_path(dir.clone()))?;
+    if file_type.is_dir() {
+      files_in(root, &path, files)?;
+    } else if root.join(&path).is_file() {
+      let name = error::result_from_option2(path.to_str(), ErrorKind::PathToStrConversionFail)
+        .map_err(|e| e.set_path(root.join(&path)))?;
+      files.push(name.replace(std::path::MAIN_SEPARATOR, "/"));
+    }
+  }
+  Ok(())
+}
+
+// the relative path of the manifest if it's in root, to leave it out of the manifest
+fn manifest_name(root: &Path, manifest: &Path) -> Option<String> {
+  let relative = manifest.strip_prefix(root).ok()?;
+  Some(relative.to_str()?.replace(std::path::MAIN_SEPARATOR, "/"))
+}
+
+// names with a '\' or a newline are escaped and the line starts with a '\'
+// like coreutils does
+fn manifest_line(checksum: &Checksum, name: &str) -> String {
+  if name.contains(['\\', '\n', '\r']) {
+    let escaped = name
+      .replace('\\', "\\\\")
+      .replace('\n', "\\n")
+      .replace('\r', "\\r");
+    format!("\\{}  {}\n", checksum, escaped)
+  } else {
+    format!("{}  {}\n", checksum, name)
+  }
+}
+
+fn unescape(name: &str) -> Option<String> {
+  let mut res = String::new();
+  let mut chars = name.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      res.push(c);
+      continue;
+    }
+    match chars.next()? {
+      '\\' => res.push('\\'),
+      'n' => res.push('\n'),
+      'r' => res.push('\r'),
+      _ => return None,
+    }
+  }
+  Some(res)
+}
+
+// parses a line like "<hex>  <name>" (or "<hex> *<name>" written in binary mode)
+fn parse_line(line: &str) -> Option<(&str, String)> {
+  let (escaped, line) = match line.strip_prefix('\\') {
+    Some(line) => (true, line),
+    None => (false, line),
+  };
+  let space = line.find(' ')?;
+  let (hex, rest) = line.split_at(space);
+  let name = rest
+    .strip_prefix("  ")
+    .or_else(|| rest.strip_prefix(" *"))?;
+  let name = name.strip_prefix("./").unwrap_or(name);
+  if hex.is_empty() || name.is_empty() {
+    return None;
+  }
+  if escaped {
+    Some((hex, unescape(name)?))
+  } else {
+    Some((hex, name.to_string()))
+  }
+}
+
+pub(crate) fn write_manifest(
+  root: &Path,
+  manifest: &Path,
+  algorithm: Algorithm,
+) -> error::Result<()> {
+  let skip = manifest_name(root, manifest);
+  let mut files = vec![];
+  files_in(root, Path::new(""), &mut files)?;
+  files.sort();
+  let mut content = String::new();
+  for name in files {
+    if Some(&name) == skip.as_ref() {
+      continue;
+    }
+    let checksum = hash_file(&root.join(&name), algorithm)?;
+    content.push_str(&manifest_line(&checksum, &name));
+  }
+  error::result_from_io(fs::write(manifest, content))
+    .map_err(|e| e.set_path(manifest.to_path_buf()))
+}
+
+pub(crate) fn verify_manifest(
+  root: &Path,
+  manifest: &Path,
+  algorithm: Option<Algorithm>,
+) -> error::Result<ManifestReport> {
+  let content = error::result_from_io(fs::read_to_string(manifest))
+    .map_err(|e| e.set_path(manifest.to_path_buf()))?;
+  let invalid_line = |number: usize, message: &str| {
+    Error::new2(
+      ErrorKind::InvalidFile,
+      format!("line {} of the manifest {}", number + 1, message),
+    )
+    .set_path(manifest.to_path_buf())
+  };
+  let mut listed = vec![];
+  for (number, line) in content.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let (hex, name) = parse_line(line).ok_or_else(|| invalid_line(number, "is invalid"))?;
+    // the algorithm is found from the length of the checksum
+    let algorithm = match algorithm {
+      Some(algorithm) => algorithm,
+      None => match hex.len() {
+        64 => Algorithm::Sha256,
+        40 => Algorithm::Sha1,
+        8 => Algorithm::Crc32,
+        _ => return Err(invalid_line(number, "has a checksum of unknown length")),
+      },
+    };
+    let checksum = Checksum::from_hex(algorithm, hex)
+      .map_err(|e| invalid_line(number, &format!("is invalid: {}", e)))?;
+    listed.push((name, checksum));
+  }
+  let mut report = ManifestReport::default();
+  for (name, checksum) in &listed {
+    let path = root.join(name);
+    if !path.is_file() {
+      report.missing.push(PathBuf::from(name));
+    } else if hash_file(&path, checksum.algorithm())? != *checksum {
+      report.mismatched.push(PathBuf::from(name));
+    }
+  }
+  let skip = manifest_name(root, manifest);
+  let names: HashSet<&String> = listed.iter().map(|(name, _)| name).collect();
+  let mut files = vec![];
+  files_in(root, Path::new(""), &mut files)?;
+  files.sort();
+  for name in files {
+    if Some(&name) != skip.as_ref() && !names.contains(&name) {
+      report.extra.push(PathBuf::from(name));
+    }
+  }
+  Ok(report)
+}
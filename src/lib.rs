@@ -49,13 +49,20 @@ mod dir;
 /// a module that has every thing about errors
 pub mod error;
 mod file;
+/// hash algorithms, checksums and checksum manifests, see `File::hash` and
+/// `Dir::write_manifest`
+#[cfg(feature = "hash")]
+pub mod hash;
+mod lock;
 mod path_stuff;
+mod pattern;
 /// this module contains every thing about Shape
 pub mod shape;
 
 pub use dir::Dir;
 pub use dir::DirEntry;
-pub use file::File;
+pub use file::{Chunks, File, Lines};
 pub use fs_pro_macros::Shape;
+pub use lock::{DirLock, FileLock};
 pub use path_stuff::{ParsedPathDir, ParsedPathFile};
 pub use shape::Shape;
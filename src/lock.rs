@@ -0,0 +1,259 @@
+use crate::error::{self, Error, ErrorKind};
+use crate::path_stuff;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// the name of the lockfile created by `Dir::lock`
+pub const LOCKFILE_NAME: &str = ".lock";
+
+// a lockfile older than this is stale when it's owner can't be checked
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+// how often `Dir::lock` checks the lockfile while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// numbers the locks of the process so two of them never have the same owner
+static NEXT_LOCK: AtomicU64 = AtomicU64::new(0);
+
+/// an advisory lock on a file, it's released when dropped
+///
+/// see `File::lock_exclusive` and `File::lock_shared`
+#[derive(Debug)]
+pub struct FileLock {
+  file: fs::File,
+  path: PathBuf,
+}
+
+impl FileLock {
+  /// the locked file, reads and writes through it don't need to open the file again
+  pub fn file(&self) -> &fs::File {
+    &self.file
+  }
+  /// releases the lock, unlike dropping the lock it returns errors
+  pub fn unlock(self) -> error::Result<()> {
+    error::result_from_io(self.file.unlock()).map_err(|e| e.set_path(self.path.clone()))
+  }
+}
+
+impl Drop for FileLock {
+  fn drop(&mut self) {
+    // closing the file releases the lock anyway
+    let _ = self.file.unlock();
+  }
+}
+
+fn open(path: &Path) -> error::Result<fs::File> {
+  error::result_from_io(
+    fs::OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(path),
+  )
+  .map_err(|e| e.set_path(path.to_path_buf()))
+}
+
+pub(crate) fn lock_file(path: &Path, exclusive: bool) -> error::Result<FileLock> {
+  let file = open(path)?;
+  let res = if exclusive {
+    file.lock()
+  } else {
+    file.lock_shared()
+  };
+  error::result_from_io(res).map_err(|e| e.set_path(path.to_path_buf()))?;
+  Ok(FileLock {
+    file,
+    path: path.to_path_buf(),
+  })
+}
+
+pub(crate) fn try_lock_file(path: &Path, exclusive: bool) -> error::Result<Option<FileLock>> {
+  let file = open(path)?;
+  let res = if exclusive {
+    file.try_lock()
+  } else {
+    file.try_lock_shared()
+  };
+  match res {
+    Ok(()) => Ok(Some(FileLock {
+      file,
+      path: path.to_path_buf(),
+    })),
+    Err(fs::TryLockError::WouldBlock) => Ok(None),
+    Err(fs::TryLockError::Error(e)) => Err(Error::from_io(e).set_path(path.to_path_buf())),
+  }
+}
+
+/// a lock on a folder held with a lockfile, it's released when dropped
+///
+/// see `Dir::lock`
+#[derive(Debug)]
+pub struct DirLock {
+  path: PathBuf,
+  owner: String,
+}
+
+impl DirLock {
+  /// the path of the lockfile
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+  /// releases the lock, unlike dropping the lock it returns errors
+  pub fn unlock(mut self) -> error::Result<()> {
+    let res = self.release();
+    // there's nothing left to release on drop
+    self.owner.clear();
+    res
+  }
+  fn release(&self) -> error::Result<()> {
+    // the lockfile is only removed if it's still ours, it could have been
+    // taken over as stale
+    match fs::read_to_string(&self.path) {
+      Ok(content) if content == self.owner => error::result_from_io(fs::remove_file(&self.path))
+        .map_err(|e| e.set_path(self.path.clone())),
+      _ => Ok(()),
+    }
+  }
+}
+
+impl Drop for DirLock {
+  fn drop(&mut self) {
+    if !self.owner.is_empty() {
+      let _ = self.release();
+    }
+  }
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|time| time.as_secs())
+    .unwrap_or(0)
+}
+
+// returns whether a process is running, None if it can't be checked
+fn is_alive(pid: u32) -> Option<bool> {
+  if cfg!(target_os = "linux") && Path::new("/proc/self").exists() {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+  } else {
+    None
+  }
+}
+
+// the content of a lockfile is "<pid> <unix timestamp> <number of the lock>"
+fn is_stale(content: &str, modified: Option<SystemTime>) -> bool {
+  let mut parts = content.split_whitespace();
+  let pid = parts.next().and_then(|pid| pid.parse::<u32>().ok());
+  let time = parts.next().and_then(|time| time.parse::<u64>().ok());
+  if let Some(alive) = pid.and_then(is_alive) {
+    return !alive;
+  }
+  let age = match time {
+    Some(time) => now().saturating_sub(time),
+    // a lockfile that isn't fully written yet is only stale if it stays like that
+    None => match modified.and_then(|modified| modified.elapsed().ok()) {
+      Some(age) => age.as_secs(),
+      None => return false,
+    },
+  };
+  age > STALE_AFTER.as_secs()
+}
+
+fn create_lockfile(path: &Path, owner: &str) -> error::Result<bool> {
+  let file = fs::OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(path);
+  let mut file = match file {
+    Ok(file) => file,
+    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+    Err(e) => return Err(Error::from_io(e).set_path(path.to_path_buf())),
+  };
+  if let Err(e) = file.write_all(owner.as_bytes()) {
+    let _ = fs::remove_file(path);
+    return Err(Error::from_io(e).set_path(path.to_path_buf()));
+  }
+  Ok(true)
+}
+
+// removes a stale lockfile, it's moved away first so a lockfile created by
+// another process in the meantime is put back instead of removed
+fn remove_stale(path: &Path, content: &str) -> error::Result<()> {
+  let stale = path.with_file_name(format!(
+    "{}.stale-{}-{}",
+    LOCKFILE_NAME,
+    std::process::id(),
+    path_stuff::get_rand_chars(6)
+  ));
+  if fs::rename(path, &stale).is_err() {
+    // someone else removed it first
+    return Ok(());
+  }
+  let mut moved = String::new();
+  let read = fs::File::open(&stale).and_then(|mut file| file.read_to_string(&mut moved));
+  if read.is_ok() && moved == content {
+    return error::result_from_io(fs::remove_file(&stale)).map_err(|e| e.set_path(stale));
+  }
+  // it was taken over in the meantime, put it back unless a new lockfile exists
+  let _ = fs::hard_link(&stale, path);
+  let _ = fs::remove_file(&stale);
+  Ok(())
+}
+
+pub(crate) fn try_lock_dir(dir: &Path) -> error::Result<Option<DirLock>> {
+  if !dir.is_dir() {
+    return Err(Error::new_from_kind(ErrorKind::NotFound).set_path(dir.to_path_buf()));
+  }
+  let path = dir.join(LOCKFILE_NAME);
+  let owner = format!(
+    "{} {} {}\n",
+    std::process::id(),
+    now(),
+    NEXT_LOCK.fetch_add(1, Ordering::Relaxed)
+  );
+  for _ in 0..2 {
+    if create_lockfile(&path, &owner)? {
+      return Ok(Some(DirLock { path, owner }));
+    }
+    let content = match fs::read_to_string(&path) {
+      Ok(content) => content,
+      // it was removed in the meantime, try again
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(Error::from_io(e).set_path(path)),
+    };
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    if !is_stale(&content, modified) {
+      return Ok(None);
+    }
+    remove_stale(&path, &content)?;
+  }
+  Ok(None)
+}
+
+pub(crate) fn lock_dir(dir: &Path) -> error::Result<DirLock> {
+  loop {
+    if let Some(lock) = try_lock_dir(dir)? {
+      return Ok(lock);
+    }
+    thread::sleep(POLL_INTERVAL);
+  }
+}
+
+pub(crate) fn lock_dir_timeout(dir: &Path, timeout: Duration) -> error::Result<Option<DirLock>> {
+  let deadline = Instant::now() + timeout;
+  loop {
+    if let Some(lock) = try_lock_dir(dir)? {
+      return Ok(Some(lock));
+    }
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+      return Ok(None);
+    }
+    thread::sleep(left.min(POLL_INTERVAL));
+  }
+}
@@ -1,7 +1,34 @@
-mod dir;
-mod error;
-mod file;
-mod path_stuff;
-mod shape;
+use fs_pro::{error, shape::DynShape, Dir};
+use std::process::exit;
 
-fn main() {}
+const USAGE: &str = "usage: fs_pro infer <dir> [struct name]";
+
+// prints the #[derive(Shape)] source of the shape of a directory
+fn infer(path: &str, name: &str) -> error::Result<String> {
+  DynShape::infer(&Dir::new(path)?)?.to_rust(name)
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match args
+    .iter()
+    .map(String::as_str)
+    .collect::<Vec<_>>()
+    .as_slice()
+  {
+    ["infer", path] | ["infer", path, _] => {
+      let name = args.get(2).map(String::as_str).unwrap_or("Root");
+      match infer(path, name) {
+        Ok(source) => print!("{}", source),
+        Err(e) => {
+          eprintln!("error: {}", e.message);
+          exit(1);
+        }
+      }
+    }
+    _ => {
+      eprintln!("{}", USAGE);
+      exit(2);
+    }
+  }
+}
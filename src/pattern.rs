@@ -0,0 +1,39 @@
+use crate::error;
+use globset::GlobMatcher;
+use std::ffi::OsStr;
+
+/// a compiled glob pattern used by `#[pattern]` and `#[rest_pattern]`
+///
+/// the pattern must match the whole file name, it supports
+/// - `*` any sequence of characters
+/// - `?` any single character
+/// - `[abc]`, `[a-z]` and `[!abc]` character classes
+/// - `{a,b}` alternation
+/// - a leading `!` which negates the whole pattern
+///
+/// fs_pro_macros checks patterns at compile time with the same rules
+#[derive(Debug, Clone)]
+pub struct Pattern {
+  matcher: GlobMatcher,
+  negated: bool,
+}
+
+impl Pattern {
+  /// compiles the pattern
+  pub fn new(pattern: &str) -> error::Result<Pattern> {
+    match fs_pro_pattern::compile(pattern) {
+      Ok((negated, glob)) => Ok(Pattern {
+        matcher: glob.compile_matcher(),
+        negated,
+      }),
+      Err(e) => Err(error::Error::new2(
+        error::ErrorKind::InvalidPattern,
+        format!("invalid pattern \"{}\": {}", pattern, e),
+      )),
+    }
+  }
+  /// returns true if the name matches the pattern
+  pub fn is_match<N: AsRef<OsStr>>(&self, name: N) -> bool {
+    self.matcher.is_match(name.as_ref()) != self.negated
+  }
+}
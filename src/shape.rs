@@ -1,23 +1,189 @@
 #![allow(dead_code)]
 use crate::error;
-use crate::{dir::Dir, file::File};
-use std::ffi::OsString;
+use crate::pattern::Pattern;
+use crate::{dir::Dir, dir::DirEntry, file::File};
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 
+mod builder;
+mod diff;
+#[cfg(feature = "serde")]
+mod format;
+mod infer;
+mod instance;
+mod journal;
+mod migrate;
+mod mode;
+mod params;
+mod repair;
+mod report;
+mod validator;
+
+pub use builder::{DynShape, DynShapeInst, DynShapeInstItem, ShapeBuilder};
+pub use diff::{ChangeKind, ShapeChange, ShapeDiff};
+#[cfg(feature = "serde")]
+pub use format::{ShapeDef, ShapeEntryDef};
+pub use instance::ShapeInstance;
+pub use migrate::{Migrate, Migration, MigrationStep};
+pub use mode::ModeRequirement;
+pub use params::ShapeContext;
+pub use repair::{RepairAction, RepairLog, RepairPolicy, ViolatingEntries};
+pub use report::{EntryType, ValidationReport, Violation, ViolationKind};
+pub use validator::ContentValidator;
+
 #[derive(PartialEq)]
 #[allow(missing_docs)]
 pub enum ShapeItemStatic<'a> {
-  File(&'a str, &'a str),
-  DirectoryPattern(&'a str, &'a str, &'a str),
-  DirectorySchema(&'a str, &'a str, &'a ShapeSchemaStatic<'a>),
+  File(&'a str, &'a str, ShapeItemOptions<'a>),
+  DirectoryPattern(&'a str, &'a str, &'a str, ShapeItemOptions<'a>),
+  DirectorySchema(
+    &'a str,
+    &'a str,
+    &'a ShapeSchemaStatic<'a>,
+    ShapeItemOptions<'a>,
+  ),
+  DirectorySchemaPattern(
+    &'a str,
+    &'a str,
+    &'a ShapeSchemaStatic<'a>,
+    ShapeItemOptions<'a>,
+  ),
+  Rest(ShapeRest<'a>),
+  /// the folder matches one of the schemas, it's the schema of a `#[derive(Shape)]` enum
+  Variants(&'a [(&'a str, &'a ShapeSchemaStatic<'a>)]),
+}
+
+/// options of a single entry in a shape
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShapeItemOptions<'a> {
+  /// the entry may be missing, it's set by using `Option<File>`, `Option<Dir>`
+  /// or `Option<YourShape>` as the type of the field
+  pub optional: bool,
+  /// the initial content of a file, it's set by `#[content = "..."]`
+  /// or `#[content_file = "path"]`
+  pub content: Option<Cow<'a, [u8]>>,
+  /// the checks of the content of a file, see `ContentValidator`
+  pub validators: Cow<'a, [ContentValidator]>,
+  /// the permissions of the entry, see `ModeRequirement`
+  pub mode: ModeRequirement,
+}
+
+impl<'a> ShapeItemOptions<'a> {
+  /// the options of a plain required entry
+  pub const DEFAULT: ShapeItemOptions<'a> = ShapeItemOptions {
+    optional: false,
+    content: None,
+    validators: Cow::Borrowed(&[]),
+    mode: ModeRequirement::NONE,
+  };
+  /// converts the options to options that own their data
+  pub fn into_owned(self) -> ShapeItemOptions<'static> {
+    ShapeItemOptions {
+      optional: self.optional,
+      content: self.content.map(|content| Cow::Owned(content.into_owned())),
+      validators: Cow::Owned(self.validators.into_owned()),
+      mode: self.mode,
+    }
+  }
+}
+
+/// options used by `Shape::create_at_with_options`
+#[derive(Debug, Clone)]
+pub struct CreateOptions {
+  /// create optional entries too (default: false)
+  pub create_optional: bool,
+  /// write the content of files that already exist and are not empty (default: false)
+  pub overwrite: bool,
+}
+
+impl CreateOptions {
+  /// creates the default options
+  pub fn new() -> Self {
+    CreateOptions {
+      create_optional: false,
+      overwrite: false,
+    }
+  }
+}
+
+impl Default for CreateOptions {
+  fn default() -> Self {
+    CreateOptions::new()
+  }
+}
+
+/// an event sent to the hook of `Shape::create_at_hook`, it holds the path of the entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeEvent {
+  /// a file was created
+  FileCreated(PathBuf),
+  /// a file already existed
+  FileExisted(PathBuf),
+  /// a folder was created
+  DirCreated(PathBuf),
+  /// a folder already existed
+  DirExisted(PathBuf),
+  /// the content of a file was written (see `#[content]`)
+  ContentWritten(PathBuf),
+  /// an optional entry that doesn't exist was not created
+  Skipped(PathBuf),
+}
+
+/// returned by the hook of `Shape::create_at_hook`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeHookResult {
+  /// continue creating the shape
+  Continue,
+  /// stop and roll back the creation
+  Abort,
+}
+
+/// describes how entries that are not declared in a shape (the `__rest` of the directory)
+/// are treated by `Shape::validate`
+///
+/// set it on a shape struct with `#[rest = "forbid"]`, `#[rest = "allow"]`
+/// or `#[rest_pattern = "*.md"]`
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ShapeRest<'a> {
+  /// any undeclared file or folder is allowed (the default)
+  Allow,
+  /// any undeclared file or folder is an error
+  Forbid,
+  /// undeclared files and folders are allowed only if their name matches the pattern
+  Pattern(Cow<'a, str>),
+}
+
+impl ShapeRest<'_> {
+  /// converts the rest to one that owns it's pattern
+  pub fn into_owned(self) -> ShapeRest<'static> {
+    match self {
+      ShapeRest::Allow => ShapeRest::Allow,
+      ShapeRest::Forbid => ShapeRest::Forbid,
+      ShapeRest::Pattern(pattern) => ShapeRest::Pattern(Cow::Owned(pattern.into_owned())),
+    }
+  }
 }
 
-impl ShapeItemStatic<'_> {
-  fn identifier(&self) -> &&str {
+impl<'a> ShapeItemStatic<'a> {
+  fn identifier(&self) -> &str {
     match self {
-      ShapeItemStatic::File(identifier, _)
-      | ShapeItemStatic::DirectoryPattern(identifier, _, _)
-      | ShapeItemStatic::DirectorySchema(identifier, _, _) => identifier,
+      ShapeItemStatic::File(identifier, _, _)
+      | ShapeItemStatic::DirectoryPattern(identifier, _, _, _)
+      | ShapeItemStatic::DirectorySchema(identifier, _, _, _)
+      | ShapeItemStatic::DirectorySchemaPattern(identifier, _, _, _) => identifier,
+      ShapeItemStatic::Rest(_) => "__rest",
+      ShapeItemStatic::Variants(_) => "__variants",
+    }
+  }
+  fn options(&self) -> ShapeItemOptions<'a> {
+    match self {
+      ShapeItemStatic::File(_, _, options)
+      | ShapeItemStatic::DirectoryPattern(_, _, _, options)
+      | ShapeItemStatic::DirectorySchema(_, _, _, options)
+      | ShapeItemStatic::DirectorySchemaPattern(_, _, _, options) => options.clone(),
+      ShapeItemStatic::Rest(_) | ShapeItemStatic::Variants(_) => ShapeItemOptions::DEFAULT,
     }
   }
 }
@@ -25,27 +191,154 @@ impl ShapeItemStatic<'_> {
 #[allow(missing_docs)]
 pub type ShapeSchemaStatic<'a> = [ShapeItemStatic<'a>];
 
+/// an entry of an owned shape schema, it's the runtime version of `ShapeItemStatic`
+///
+/// use `ShapeBuilder` to build a schema or `schema_from_static` to convert
+/// the schema of a `#[derive(Shape)]` struct
+#[derive(Debug, PartialEq, Clone)]
+#[allow(missing_docs)]
+pub enum ShapeItem {
+  File(String, String, ShapeItemOptions<'static>),
+  DirectoryPattern(String, String, String, ShapeItemOptions<'static>),
+  DirectorySchema(String, String, ShapeSchema, ShapeItemOptions<'static>),
+  DirectorySchemaPattern(String, String, ShapeSchema, ShapeItemOptions<'static>),
+  Rest(ShapeRest<'static>),
+  /// the folder matches one of the schemas, each with the name of it's variant
+  Variants(Vec<(String, ShapeSchema)>),
+}
+
+/// an owned shape schema
+pub type ShapeSchema = Vec<ShapeItem>;
+
+impl ShapeItem {
+  /// the identifier of the entry (the name of the field in a `#[derive(Shape)]` struct)
+  pub fn identifier(&self) -> &str {
+    match self {
+      ShapeItem::File(identifier, _, _)
+      | ShapeItem::DirectoryPattern(identifier, _, _, _)
+      | ShapeItem::DirectorySchema(identifier, _, _, _)
+      | ShapeItem::DirectorySchemaPattern(identifier, _, _, _) => identifier,
+      ShapeItem::Rest(_) => "__rest",
+      ShapeItem::Variants(_) => "__variants",
+    }
+  }
+  /// the file name of the entry, None for pattern matched directories and rest
+  pub fn name(&self) -> Option<&str> {
+    match self {
+      ShapeItem::File(_, name, _)
+      | ShapeItem::DirectoryPattern(_, name, _, _)
+      | ShapeItem::DirectorySchema(_, name, _, _) => Some(name),
+      ShapeItem::DirectorySchemaPattern(_, _, _, _)
+      | ShapeItem::Rest(_)
+      | ShapeItem::Variants(_) => None,
+    }
+  }
+  /// the options of the entry
+  pub fn options(&self) -> &ShapeItemOptions<'static> {
+    match self {
+      ShapeItem::File(_, _, options)
+      | ShapeItem::DirectoryPattern(_, _, _, options)
+      | ShapeItem::DirectorySchema(_, _, _, options)
+      | ShapeItem::DirectorySchemaPattern(_, _, _, options) => options,
+      ShapeItem::Rest(_) | ShapeItem::Variants(_) => &ShapeItemOptions::DEFAULT,
+    }
+  }
+}
+
+impl From<&ShapeItemStatic<'_>> for ShapeItem {
+  fn from(item: &ShapeItemStatic<'_>) -> Self {
+    match item {
+      ShapeItemStatic::File(identifier, name, options) => ShapeItem::File(
+        identifier.to_string(),
+        name.to_string(),
+        options.clone().into_owned(),
+      ),
+      ShapeItemStatic::DirectoryPattern(identifier, name, pattern, options) => {
+        ShapeItem::DirectoryPattern(
+          identifier.to_string(),
+          name.to_string(),
+          pattern.to_string(),
+          options.clone().into_owned(),
+        )
+      }
+      ShapeItemStatic::DirectorySchema(identifier, name, schema, options) => {
+        ShapeItem::DirectorySchema(
+          identifier.to_string(),
+          name.to_string(),
+          schema_from_static(schema),
+          options.clone().into_owned(),
+        )
+      }
+      ShapeItemStatic::DirectorySchemaPattern(identifier, pattern, schema, options) => {
+        ShapeItem::DirectorySchemaPattern(
+          identifier.to_string(),
+          pattern.to_string(),
+          schema_from_static(schema),
+          options.clone().into_owned(),
+        )
+      }
+      ShapeItemStatic::Rest(rest) => ShapeItem::Rest(rest.clone().into_owned()),
+      ShapeItemStatic::Variants(variants) => ShapeItem::Variants(
+        variants
+          .iter()
+          .map(|(name, schema)| (name.to_string(), schema_from_static(schema)))
+          .collect(),
+      ),
+    }
+  }
+}
+
+/// converts the schema of a `#[derive(Shape)]` struct to an owned schema
+/// ```
+/// use fs_pro::shape::{schema_from_static, ShapeDescribe};
+/// use fs_pro::{File, Shape};
+///
+/// #[derive(Shape)]
+/// struct MyShapedDir {
+///   #[name = "my_file.txt"]
+///   pub my_file: File,
+/// }
+///
+/// let schema = schema_from_static(MyShapedDir::shape_describe());
+/// assert_eq!(schema.len(), 1);
+/// ```
+pub fn schema_from_static(schema: &ShapeSchemaStatic<'_>) -> ShapeSchema {
+  schema.iter().map(ShapeItem::from).collect()
+}
+
 #[derive(Debug)]
 #[allow(missing_docs)]
 pub enum ShapeInstItem {
   File(File),
   Directory(Dir),
   ShapedDirectory(ShapeInst),
+  /// every folder matching the pattern of a `Vec<_>` or `HashMap<String, _>` field
+  /// with it's name, sorted by name
+  ShapedDirectories(Vec<(String, ShapeInst)>),
+  /// an optional entry that doesn't exist
+  Missing,
+  /// the index of the variant the folder matches with it's entries
+  Variant(usize, ShapeInst),
+  /// the folder of the instance, it's the first item of every instance built by
+  /// `create_at` or `open` and isn't an entry of the schema
+  Root(Dir),
 }
 
 #[allow(missing_docs)]
 pub type ShapeInst = Vec<ShapeInstItem>;
 
 #[allow(missing_docs)]
-pub trait ShapeDescribe {
+pub trait ShapeDescribe: Sized {
   fn shape_describe() -> &'static ShapeSchemaStatic<'static>;
-  fn shape_new(inst: ShapeInst) -> Self;
+  /// builds the instance from the entries bound by `create_at` or `open`, fails with
+  /// `InvalidFolder` if inst doesn't match the shape
+  fn shape_new(inst: ShapeInst) -> error::Result<Self>;
 }
 
 #[allow(missing_docs)]
-pub trait ShapeDescribeStatic {
+pub trait ShapeDescribeStatic: Sized {
   fn shape_describe() -> &'static ShapeSchemaStatic<'static>;
-  fn shape_new(inst: ShapeInst) -> Self;
+  fn shape_new(inst: ShapeInst) -> error::Result<Self>;
 }
 
 /// Shape is a struct used to create directory with a specified Shape
@@ -59,6 +352,30 @@ pub trait ShapeDescribeStatic {
 /// - a directory that will contain
 ///   - a file named "child_file.txt" and it's identifier will be "child_file"
 ///
+/// fields of type `Option<File>`, `Option<Dir>` or `Option<ChildShapedDir>` are optional,
+/// they are not created by `create_at` and `validate` doesn't complain if they are missing
+///
+/// fields of type `Vec<ChildShapedDir>` or `HashMap<String, ChildShapedDir>` with a
+/// `#[pattern = "..."]` hold every folder which name matches the pattern, each of them
+/// must match `ChildShapedDir`
+///
+/// files are created empty unless they have a `#[content = "..."]` or
+/// `#[content_file = "path"]` (read with `include_bytes!`), the content is only
+/// written to files that are empty (see `CreateOptions::overwrite`)
+///
+/// entries that are not declared are allowed by default, use `#[rest = "forbid"]`
+/// or `#[rest_pattern = "*.md"]` on the struct to restrict them (see `ShapeRest`)
+///
+/// a `#[root] pub root: Dir` field is set to the folder of the instance, the struct
+/// implements `ShapeInstance` (`root`, `copy`, `move_to` and `delete`) only if it has one
+///
+/// `#[derive(Shape)]` can also be used on an enum which variants each hold a shape
+/// (like `Python(PythonPackage)`), the folder must match one of them, `validate`
+/// reports the errors of the closest variant if none matches, `open` returns the
+/// matching variant and `create_at` creates the first one unless another one matches
+/// and with `#[instance]` on the enum it implements `ShapeInstance`, then every
+/// variant must hold a shape with a `#[root]` field
+///
 /// example:
 /// ```
 /// use fs_pro::{File, Dir, Shape};
@@ -98,25 +415,150 @@ impl<T: ShapeDescribe> Shape<T> {
   pub fn new() -> Self {
     Self { ignore_me: None }
   }
+  /// returns the schema of the shape as an owned schema
+  pub fn schema(&self) -> ShapeSchema {
+    schema_from_static(T::shape_describe())
+  }
   /// create the shape in a directory
+  ///
+  /// it's all or nothing, if any entry can't be created every file and folder created
+  /// (and content written) by the call is rolled back, entries that existed before are
+  /// left untouched, the message of the error lists anything that couldn't be rolled back
   pub fn create_at<'a, P: 'a + AsRef<Path>>(&self, path: P) -> Result<T, error::Error> {
-    let target = T::shape_describe();
-    let mut path_buf = PathBuf::new();
-    path_buf.push(path);
-    let res = create_shape_inst(path_buf, target, true, None)?;
-    Ok(T::shape_new(res))
+    self.create_at_with_options(path, &CreateOptions::new())
   }
+  /// like `create_at` but with options
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct MyShapedDir {
+  /// #   #[name = "my_file.txt"]
+  /// #   pub my_file: File,
+  /// # }
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::CreateOptions;
   ///
-  pub fn create_at_hook<'a, P: 'a + AsRef<Path>>(
+  /// # let shape: Shape<MyShapedDir> = Shape::new();
+  /// let mut options = CreateOptions::new();
+  /// options.create_optional = true;
+  /// let shape_inst = shape.create_at_with_options("target", &options)?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn create_at_with_options<'a, P: 'a + AsRef<Path>>(
     &self,
     path: P,
-    hook: &'a dyn Fn(PathBuf, bool),
+    options: &CreateOptions,
   ) -> Result<T, error::Error> {
-    let target = T::shape_describe();
-    let mut path_buf = PathBuf::new();
-    path_buf.push(path);
-    let res = create_shape_inst(path_buf, target, true, Some(hook))?;
-    Ok(T::shape_new(res))
+    self.create_at_hook(path, options, |_| ShapeHookResult::Continue)
+  }
+  /// like `create_at_with_options` but calls hook with an event for every entry
+  ///
+  /// if hook returns `ShapeHookResult::Abort` the creation stops, it's rolled back
+  /// and an `Interrupted` error is returned
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct MyShapedDir {
+  /// #   #[name = "my_file.txt"]
+  /// #   pub my_file: File,
+  /// # }
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::{CreateOptions, ShapeEvent, ShapeHookResult};
+  ///
+  /// # let shape: Shape<MyShapedDir> = Shape::new();
+  /// let mut created = 0;
+  /// let shape_inst = shape.create_at_hook("target", &CreateOptions::new(), |event| {
+  ///   if let ShapeEvent::FileCreated(_) | ShapeEvent::DirCreated(_) = event {
+  ///     created += 1;
+  ///   }
+  ///   ShapeHookResult::Continue
+  /// })?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn create_at_hook<'a, P, F>(
+    &self,
+    path: P,
+    options: &CreateOptions,
+    mut hook: F,
+  ) -> Result<T, error::Error>
+  where
+    P: 'a + AsRef<Path>,
+    F: FnMut(ShapeEvent) -> ShapeHookResult,
+  {
+    let res = create_at(path, &params::literal(&self.schema())?, options, &mut hook)?;
+    T::shape_new(res)
+  }
+  /// like `create_at` but fills the placeholders in the names of the entries from ctx
+  ///
+  /// a placeholder is a key in braces, it's only replaced in names (patterns are globs
+  /// where `{a,b}` is an alternation), `{{` and `}}` are literal braces in every method
+  /// and the methods without a context fail with `InvalidSchema` if a name has a
+  /// placeholder
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::ShapeContext;
+  /// use fs_pro::{File, Shape};
+  ///
+  /// #[derive(Shape)]
+  /// struct AppConfig {
+  ///   #[name = "{app}.conf"]
+  ///   pub config: File,
+  /// }
+  ///
+  /// let shape: Shape<AppConfig> = Shape::new();
+  /// let mut ctx = ShapeContext::new();
+  /// ctx.insert("app".to_string(), "nginx".to_string());
+  /// let shape_inst = shape.create_at_with("/etc", &ctx)?; // creates /etc/nginx.conf
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn create_at_with<'a, P: 'a + AsRef<Path>>(
+    &self,
+    path: P,
+    ctx: &ShapeContext,
+  ) -> Result<T, error::Error> {
+    let schema = params::resolve(&self.schema(), ctx)?;
+    let res = create_at(path, &schema, &CreateOptions::new(), &mut |_| {
+      ShapeHookResult::Continue
+    })?;
+    T::shape_new(res)
+  }
+  /// like `open` but fills the placeholders in names from ctx, see `create_at_with`
+  pub fn open_with<'a, P: 'a + AsRef<Path>>(
+    &self,
+    path: P,
+    ctx: &ShapeContext,
+  ) -> Result<T, Errors> {
+    let schema = params::resolve(&self.schema(), ctx).map_err(|e| vec![e])?;
+    let res = open(path, &schema)?;
+    T::shape_new(res).map_err(|e| vec![e])
+  }
+  /// binds an existing directory to the shape without creating anything
+  ///
+  /// the directory is validated first (see `validate`) and the errors are returned
+  /// if it doesn't match the shape
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct MyShapedDir {
+  /// #   #[name = "my_file.txt"]
+  /// #   pub my_file: File,
+  /// # }
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// # let shape: Shape<MyShapedDir> = Shape::new();
+  /// match shape.open("target") {
+  ///   Ok(shape_inst) => println!("{}", shape_inst.my_file.read_to_string()?),
+  ///   Err(errors) => println!("target doesn't match the shape: {:?}", errors),
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn open<'a, P: 'a + AsRef<Path>>(&self, path: P) -> Result<T, Errors> {
+    let schema = params::literal(&self.schema()).map_err(|e| vec![e])?;
+    let res = open(path, &schema)?;
+    T::shape_new(res).map_err(|e| vec![e])
   }
   /// checks if a folder matches the shape and returns list of errors if they don't match
   /// the errors specify why and where they are not matching
@@ -125,144 +567,638 @@ impl<T: ShapeDescribe> Shape<T> {
   /// - Interrupted      if operation interrupts (aka been stooped)
   /// - InvalidFile      if a file that doesn't match the pattern in a pattern dir was found
   /// - InvalidFolder    if a folder was found in a pattern dir
+  /// - InvalidFile      if a file was found where a folder is declared
+  /// - InvalidFolder    if a folder was found where a file is declared
   /// - PermissionDenied if the os refuses to give the program permission to read form disk
+  /// - InvalidFile      if an undeclared file was found and `__rest` forbids it
+  /// - InvalidFolder    if an undeclared folder was found and `__rest` forbids it
   /// any other error that's not listed is IMPOSSIBLE to occur
+  ///
+  /// use `validate_report` to get typed violations instead
   pub fn validate<'a, P: 'a + AsRef<Path>>(&self, path: P) -> Result<(), Errors> {
-    let mut errors: Errors = vec![];
-    let dir = Dir::new(path);
-    if let Err(e) = dir {
-      errors.push(e);
-      return Err(errors);
-    }
-    let dir = dir.unwrap();
-    let target = T::shape_describe();
-    let errors = validate_dir(dir, target);
-    if errors.len() > 0 {
-      return Err(errors);
+    let schema = params::literal(&self.schema()).map_err(|e| vec![e])?;
+    validate(path, &schema)
+  }
+  /// like `validate` but fills the placeholders in names from ctx, see `create_at_with`
+  pub fn validate_with<'a, P: 'a + AsRef<Path>>(
+    &self,
+    path: P,
+    ctx: &ShapeContext,
+  ) -> Result<(), Errors> {
+    let schema = params::resolve(&self.schema(), ctx).map_err(|e| vec![e])?;
+    validate(path, &schema)
+  }
+  /// like `validate` but returns a report with a typed violation for every mismatch
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct MyShapedDir {
+  /// #   #[name = "my_file.txt"]
+  /// #   pub my_file: File,
+  /// # }
+  /// # let shape: Shape<MyShapedDir> = Shape::new();
+  /// let report = shape.validate_report("target");
+  /// for violation in &report.violations {
+  ///   println!("{}: {}", violation.identifier, violation.message());
+  /// }
+  /// ```
+  pub fn validate_report<'a, P: 'a + AsRef<Path>>(&self, path: P) -> ValidationReport {
+    match params::literal(&self.schema()) {
+      Ok(schema) => validate_report(path, &schema),
+      Err(e) => invalid_schema_report(path.as_ref(), e),
     }
-    Ok(())
   }
-}
-
-fn check_pattern(pattern: &&str, path: OsString) -> bool {
-  let pattern = pattern.replace(".", "\\.").replace("*", ".*");
-  let regex = regex::Regex::new(pattern.as_str()).unwrap();
-  if !regex.is_match(path.to_string_lossy().as_ref()) {
-    return false;
+  /// migrates a directory with the shape `Old` to this shape
+  ///
+  /// the new shape is the shape it's called on and the old one is the only type
+  /// parameter, so the call is `Shape::<New>::new().migrate::<Old>(path)` (there is
+  /// no `Shape::migrate::<Old, New>(path)`, the new shape is always known from `Shape`)
+  ///
+  /// the steps of `Old::migration()` are run in order, then the missing entries of the
+  /// shape are created (like `create_at`) and the directory is validated, if any of
+  /// them fails every step is rolled back
+  ///
+  /// deleted entries are moved into a backup folder named
+  /// `.{name}.migration-backup-{pid}` in the parent of the directory (so they can be
+  /// renamed on the same file system) and it's removed once the migration is done, this
+  /// means a migration that deletes entries fails if the parent isn't writable and the
+  /// backup folder is left behind if the process dies during the migration
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct LayoutV1 {
+  /// #   #[name = "config.toml"]
+  /// #   pub config: File,
+  /// # }
+  /// # #[derive(Shape)]
+  /// # struct LayoutV2 {
+  /// #   #[name = "settings.toml"]
+  /// #   pub config: File,
+  /// # }
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::{Migrate, Migration};
+  ///
+  /// impl Migrate<LayoutV2> for LayoutV1 {
+  ///   fn migration() -> Migration {
+  ///     Migration::new().rename("config.toml", "settings.toml")
+  ///   }
+  /// }
+  /// let shape: Shape<LayoutV2> = Shape::new();
+  /// let layout = shape.migrate::<LayoutV1>("target")?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn migrate<Old: Migrate<T>>(&self, path: impl AsRef<Path>) -> error::Result<T> {
+    let res = migrate::migrate(path, &Old::migration(), &params::literal(&self.schema())?)?;
+    T::shape_new(res)
+  }
+  /// the structural difference between the shape and a directory, see `ShapeDiff::with_dir`
+  pub fn diff_dir<'a, P: 'a + AsRef<Path>>(&self, path: P) -> error::Result<ShapeDiff> {
+    ShapeDiff::with_dir(&params::literal(&self.schema())?, path)
+  }
+  /// fixes a directory to match the shape and returns every action taken
+  ///
+  /// missing files and folders are created (files with their content) and the modes of
+  /// entries are applied (see `ModeRequirement`), entries that violate the shape are
+  /// kept, deleted or moved as set by `policy.violating` and nothing is touched if
+  /// `policy.dry_run` is set, kept entries of the wrong type are listed in
+  /// `RepairLog::unrepairable`
+  /// ```no_run
+  /// # use fs_pro::{File, Shape};
+  /// # #[derive(Shape)]
+  /// # struct MyShapedDir {
+  /// #   #[name = "my_file.txt"]
+  /// #   pub my_file: File,
+  /// # }
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::{RepairPolicy, ViolatingEntries};
+  ///
+  /// # let shape: Shape<MyShapedDir> = Shape::new();
+  /// let mut policy = RepairPolicy::new();
+  /// policy.violating = ViolatingEntries::MoveTo("quarantine".into());
+  /// policy.dry_run = true;
+  /// print!("{}", shape.repair("target", &policy)?); // the planned actions
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn repair<'a, P: 'a + AsRef<Path>>(
+    &self,
+    path: P,
+    policy: &RepairPolicy,
+  ) -> error::Result<RepairLog> {
+    repair::repair(path, &params::literal(&self.schema())?, policy)
   }
-  true
 }
 
 use error::{Error, ErrorKind};
+use journal::{Change, Journal};
 
 type Errors = Vec<Error>;
 
-fn validate_dir(dir: Dir, target: &'static ShapeSchemaStatic<'static>) -> Errors {
-  // TODO implement __rest
-  let mut errors: Errors = vec![];
-  if !dir.exists() {
-    errors.push(Error::new_from_kind(ErrorKind::NotFound).set_path(dir.path.clone()));
-    return errors;
+fn create_at<P: AsRef<Path>>(
+  path: P,
+  target: &[ShapeItem],
+  options: &CreateOptions,
+  hook: &mut dyn FnMut(ShapeEvent) -> ShapeHookResult,
+) -> Result<ShapeInst, error::Error> {
+  let mut path_buf = PathBuf::new();
+  path_buf.push(path);
+  let mut creation = Creation {
+    create: true,
+    options,
+    hook,
+    journal: Journal::new(),
+  };
+  match create_shape_inst(path_buf, target, &mut creation) {
+    Ok(res) => Ok(res),
+    Err(e) => Err(journal::fail_with_rollback(e, creation.journal)),
+  }
+}
+
+fn open<P: AsRef<Path>>(path: P, target: &[ShapeItem]) -> Result<ShapeInst, Errors> {
+  let mut path_buf = PathBuf::new();
+  path_buf.push(path);
+  validate(&path_buf, target)?;
+  let mut creation = Creation {
+    create: false,
+    options: &CreateOptions::new(),
+    hook: &mut |_| ShapeHookResult::Continue,
+    journal: Journal::new(),
+  };
+  match create_shape_inst(path_buf, target, &mut creation) {
+    Ok(res) => Ok(res),
+    Err(e) => Err(vec![e]),
   }
+}
+
+fn validate<P: AsRef<Path>>(path: P, target: &[ShapeItem]) -> Result<(), Errors> {
+  validate_report(path, target).into_result()
+}
+
+// the report of a schema that can't be used, like one with a placeholder and no context
+fn invalid_schema_report(path: &Path, e: Error) -> ValidationReport {
+  let mut report = ValidationReport::new(path.to_path_buf());
+  let root = report.root.clone();
+  report
+    .violations
+    .push(Violation::new(ViolationKind::Unreadable(e), root, ""));
+  report
+}
+
+fn validate_report<P: AsRef<Path>>(path: P, target: &[ShapeItem]) -> ValidationReport {
+  let mut report = ValidationReport::new(path.as_ref().to_path_buf());
+  let root = report.root.clone();
+  if check_entry(&root, EntryType::Dir, "", &mut report.violations) {
+    match Dir::new(&root) {
+      Ok(dir) => validate_dir(&dir, target, "", &mut report.violations),
+      Err(e) => report
+        .violations
+        .push(Violation::new(ViolationKind::Unreadable(e), root, "")),
+    }
+  }
+  report
+}
+
+// joins the identifier of an entry to the identifier of it's parent
+fn child_identifier(parent: &str, identifier: &str) -> String {
+  if parent.is_empty() {
+    identifier.to_string()
+  } else {
+    format!("{}.{}", parent, identifier)
+  }
+}
+
+// checks that the entry exists and has the expected type
+// returns false if it doesn't, so it can't be validated any further
+fn check_entry(
+  path: &Path,
+  expected: EntryType,
+  identifier: &str,
+  violations: &mut Vec<Violation>,
+) -> bool {
+  let kind = match (EntryType::of(path), expected) {
+    (Some(found), _) if found == expected => return true,
+    (Some(found), _) => ViolationKind::WrongType { expected, found },
+    (None, EntryType::File) => ViolationKind::MissingFile,
+    (None, EntryType::Dir) => ViolationKind::MissingDir,
+  };
+  violations.push(Violation::new(kind, path.to_path_buf(), identifier));
+  false
+}
+
+// checks that the entry meets it's mode requirement (only on unix)
+fn check_mode(
+  path: &Path,
+  entry_type: EntryType,
+  requirement: &ModeRequirement,
+  identifier: &str,
+  violations: &mut Vec<Violation>,
+) {
+  #[cfg(unix)]
+  {
+    if requirement.is_empty() {
+      return;
+    }
+    let kind = match mode::mode_of(path) {
+      Ok(actual) if requirement.is_met(actual) => return,
+      Ok(actual) => ViolationKind::WrongMode {
+        entry_type,
+        expected: *requirement,
+        actual,
+      },
+      Err(e) => ViolationKind::Unreadable(e),
+    };
+    violations.push(Violation::new(kind, path.to_path_buf(), identifier));
+  }
+  #[cfg(not(unix))]
+  let _ = (path, entry_type, requirement, identifier, violations);
+}
+
+fn validate_dir(dir: &Dir, target: &[ShapeItem], parent: &str, violations: &mut Vec<Violation>) {
   for item in target {
-    match item {
-      ShapeItemStatic::File(_, name) => {
-        if !dir.entry_exists(name) {
-          errors.push(Error::new_from_kind(ErrorKind::NotFound).set_path(dir.path.join(name)));
-        }
+    let identifier = child_identifier(parent, item.identifier());
+    if let Some(name) = item.name() {
+      if item.options().optional && !dir.entry_exists(name) {
+        continue;
       }
-      ShapeItemStatic::DirectoryPattern(_, name, pattern) => {
-        if !dir.entry_exists(name) {
-          errors.push(Error::new_from_kind(ErrorKind::NotFound).set_path(dir.path.join(name)));
+    }
+    let unreadable = |e: Error, path: &Path| {
+      Violation::new(
+        ViolationKind::Unreadable(e),
+        path.to_path_buf(),
+        identifier.as_str(),
+      )
+    };
+    match item {
+      ShapeItem::File(_, name, options) => {
+        let path = dir.path.join(name);
+        if !check_entry(&path, EntryType::File, &identifier, violations) {
           continue;
         }
-        let sub_dir = dir.get_dir(name);
-        if let Err(e) = sub_dir {
-          errors.push(e);
-          continue;
+        check_mode(
+          &path,
+          EntryType::File,
+          &options.mode,
+          &identifier,
+          violations,
+        );
+        for validator in options.validators.iter() {
+          let kind = match validator.check(&path) {
+            Ok(None) => continue,
+            Ok(Some(message)) => ViolationKind::InvalidContent(message),
+            Err(e) => ViolationKind::Unreadable(e),
+          };
+          violations.push(Violation::new(kind, path.clone(), identifier.as_str()));
         }
-        let sub_dir = sub_dir.unwrap();
-        let sub_dir_read = sub_dir.read();
-        if let Err(e) = sub_dir_read {
-          errors.push(e);
+      }
+      ShapeItem::DirectoryPattern(_, name, pattern, options) => {
+        let path = dir.path.join(name);
+        if !check_entry(&path, EntryType::Dir, &identifier, violations) {
           continue;
         }
-        let sub_dir_read = sub_dir_read.unwrap();
-        for entry in sub_dir_read {
-          if !entry.path().is_file() {
-            errors.push(Error::new_from_kind(ErrorKind::InvalidFolder).set_path(entry.path()));
+        check_mode(
+          &path,
+          EntryType::Dir,
+          &options.mode,
+          &identifier,
+          violations,
+        );
+        let compiled =
+          || -> error::Result<_> { Ok((Pattern::new(pattern)?, dir.get_dir(name)?.read()?)) };
+        let (compiled, sub_dir_read) = match compiled() {
+          Ok(compiled) => compiled,
+          Err(e) => {
+            violations.push(unreadable(e, &path));
             continue;
           }
-          if !check_pattern(pattern, entry.file_name()) {
-            errors.push(
-              Error::new2(
-                ErrorKind::InvalidFile,
-                format!("file doesn't match pattern \"{}\"", pattern),
-              )
-              .set_path(entry.path()),
-            );
+        };
+        for entry in sub_dir_read {
+          let kind = if !entry.path().is_file() {
+            ViolationKind::WrongType {
+              expected: EntryType::File,
+              found: EntryType::Dir,
+            }
+          } else if !compiled.is_match(entry.file_name()) {
+            ViolationKind::PatternMismatch {
+              pattern: pattern.clone(),
+            }
+          } else {
             continue;
-          }
+          };
+          violations.push(Violation::new(kind, entry.path(), identifier.as_str()));
         }
       }
-      ShapeItemStatic::DirectorySchema(_, name, schema) => {
-        if !dir.entry_exists(name) {
-          errors.push(Error::new_from_kind(ErrorKind::NotFound).set_path(dir.path.join(name)));
+      ShapeItem::DirectorySchema(_, name, schema, options) => {
+        let path = dir.path.join(name);
+        if !check_entry(&path, EntryType::Dir, &identifier, violations) {
           continue;
         }
-        let dir_get_dir = dir.get_dir(name);
-        if let Err(e) = dir_get_dir {
-          errors.push(e);
-          continue;
+        check_mode(
+          &path,
+          EntryType::Dir,
+          &options.mode,
+          &identifier,
+          violations,
+        );
+        match dir.get_dir(name) {
+          Ok(sub_dir) => validate_dir(&sub_dir, schema, &identifier, violations),
+          Err(e) => violations.push(unreadable(e, &path)),
         }
-        let dir_get_dir = dir_get_dir.unwrap();
-        errors.append(&mut validate_dir(dir_get_dir, schema));
+      }
+      ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) => {
+        let sub_dirs =
+          Pattern::new(pattern).and_then(|pattern| matching_dirs(dir, target, &pattern));
+        match sub_dirs {
+          Ok(sub_dirs) => {
+            for sub_dir in sub_dirs {
+              let sub_identifier = match sub_dir.name() {
+                Ok(name) => format!("{}[{}]", identifier, name),
+                Err(_) => identifier.clone(),
+              };
+              validate_dir(&sub_dir, schema, &sub_identifier, violations);
+            }
+          }
+          Err(e) => violations.push(unreadable(e, &dir.path)),
+        }
+      }
+      ShapeItem::Rest(rest) => validate_rest(dir, target, rest, &identifier, violations),
+      ShapeItem::Variants(variants) => {
+        // the entries of a variant are entries of the folder itself
+        violations.extend(closest_variant(dir, variants, parent).1);
       }
     }
   }
-  errors
+}
+
+// the index of the first variant dir matches, or of the closest one (the first with
+// the fewest violations) with it's violations
+fn closest_variant(
+  dir: &Dir,
+  variants: &[(String, ShapeSchema)],
+  parent: &str,
+) -> (usize, Vec<Violation>) {
+  let mut closest: Option<(usize, Vec<Violation>)> = None;
+  for (i, (_, schema)) in variants.iter().enumerate() {
+    let mut violations = vec![];
+    validate_dir(dir, schema, parent, &mut violations);
+    if violations.is_empty() {
+      return (i, violations);
+    }
+    let closer = match &closest {
+      Some((_, closest)) => violations.len() < closest.len(),
+      None => true,
+    };
+    if closer {
+      closest = Some((i, violations));
+    }
+  }
+  closest.unwrap_or_default()
+}
+
+fn is_named(target: &[ShapeItem], entry: &DirEntry) -> bool {
+  let file_name = entry.file_name();
+  target
+    .iter()
+    .any(|item| item.name().map(|name| file_name == name).unwrap_or(false))
+}
+
+// the compiled patterns of the Vec and HashMap fields of target
+fn schema_patterns(target: &[ShapeItem]) -> error::Result<Vec<Pattern>> {
+  let mut patterns = vec![];
+  for item in target {
+    if let ShapeItem::DirectorySchemaPattern(_, pattern, _, _) = item {
+      patterns.push(Pattern::new(pattern)?);
+    }
+  }
+  Ok(patterns)
+}
+
+// returns the folders in dir (other than the named entries of target) that match the pattern
+fn matching_dirs(dir: &Dir, target: &[ShapeItem], pattern: &Pattern) -> error::Result<Vec<Dir>> {
+  let mut result = vec![];
+  for entry in dir.read()? {
+    let named = is_named(target, &entry);
+    if let DirEntry::Dir(sub_dir) = entry {
+      if !named && pattern.is_match(sub_dir.name()?) {
+        result.push(sub_dir);
+      }
+    }
+  }
+  result.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(result)
+}
+
+fn validate_rest(
+  dir: &Dir,
+  target: &[ShapeItem],
+  rest: &ShapeRest<'static>,
+  identifier: &str,
+  violations: &mut Vec<Violation>,
+) {
+  if let ShapeRest::Allow = rest {
+    return;
+  }
+  let compiled = || -> error::Result<_> {
+    let rest_pattern = match rest {
+      ShapeRest::Pattern(pattern) => Some(Pattern::new(pattern)?),
+      _ => None,
+    };
+    Ok((rest_pattern, schema_patterns(target)?, dir.read()?))
+  };
+  let (rest_pattern, patterns, dir_read) = match compiled() {
+    Ok(compiled) => compiled,
+    Err(e) => {
+      violations.push(Violation::new(
+        ViolationKind::Unreadable(e),
+        dir.path.clone(),
+        identifier,
+      ));
+      return;
+    }
+  };
+  for entry in dir_read {
+    let file_name = entry.file_name();
+    let declared = is_named(target, &entry)
+      || (entry.is_dir() && patterns.iter().any(|pattern| pattern.is_match(&file_name)));
+    if declared {
+      continue;
+    }
+    if let Some(compiled) = &rest_pattern {
+      if compiled.is_match(&file_name) {
+        continue;
+      }
+    }
+    let entry_type = if entry.is_file() {
+      EntryType::File
+    } else {
+      EntryType::Dir
+    };
+    let rest_pattern = match rest {
+      ShapeRest::Pattern(pattern) => Some(pattern.to_string()),
+      _ => None,
+    };
+    violations.push(Violation::new(
+      ViolationKind::UnexpectedEntry {
+        entry_type,
+        rest_pattern,
+      },
+      entry.path(),
+      identifier,
+    ));
+  }
+}
+
+// the state of a create_at or open call
+struct Creation<'a> {
+  // false when opening, nothing is created then
+  create: bool,
+  options: &'a CreateOptions,
+  hook: &'a mut dyn FnMut(ShapeEvent) -> ShapeHookResult,
+  journal: Journal,
+}
+
+impl Creation<'_> {
+  fn emit(&mut self, event: ShapeEvent) -> error::Result<()> {
+    match (self.hook)(event) {
+      ShapeHookResult::Continue => Ok(()),
+      ShapeHookResult::Abort => Err(Error::new(
+        ErrorKind::Interrupted,
+        "shape creation aborted by hook",
+      )),
+    }
+  }
+  fn create_file(&mut self, file: &File, content: Option<&[u8]>) -> error::Result<()> {
+    let existed = file.exists();
+    file.create()?;
+    if existed {
+      self.emit(ShapeEvent::FileExisted(file.path.clone()))?;
+    } else {
+      self.journal.push(Change::Created(file.path.clone()));
+      self.emit(ShapeEvent::FileCreated(file.path.clone()))?;
+    }
+    if let Some(content) = content {
+      if self.options.overwrite || file.size()? == 0 {
+        if existed {
+          let previous = error::result_from_io(std::fs::read(&file.path))?;
+          self
+            .journal
+            .push(Change::Overwritten(file.path.clone(), previous));
+        }
+        file.write(content)?;
+        self.emit(ShapeEvent::ContentWritten(file.path.clone()))?;
+      }
+    }
+    Ok(())
+  }
+  // changes the permissions of path to meet the requirement
+  fn apply_mode(&mut self, path: &Path, requirement: &ModeRequirement) -> error::Result<()> {
+    #[cfg(unix)]
+    {
+      if requirement.is_empty() {
+        return Ok(());
+      }
+      let previous = mode::mode_of(path)?;
+      if !requirement.is_met(previous) {
+        mode::set_mode(path, requirement.apply(previous))?;
+        self
+          .journal
+          .push(Change::ModeChanged(path.to_path_buf(), previous));
+      }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, requirement);
+    Ok(())
+  }
+  fn create_dir(&mut self, dir: &Dir) -> error::Result<()> {
+    if dir.exists() {
+      return self.emit(ShapeEvent::DirExisted(dir.path.clone()));
+    }
+    dir.create()?;
+    self.journal.push(Change::Created(dir.path.clone()));
+    self.emit(ShapeEvent::DirCreated(dir.path.clone()))
+  }
 }
 
 fn create_shape_inst(
   path_buf: PathBuf,
-  target_shape: &[ShapeItemStatic<'_>],
-  create: bool,
-  hook: Option<&dyn Fn(PathBuf, bool)>,
+  target_shape: &[ShapeItem],
+  creation: &mut Creation<'_>,
 ) -> Result<ShapeInst, error::Error> {
-  let mut res: ShapeInst = vec![];
+  let mut res: ShapeInst = vec![ShapeInstItem::Root(Dir::new(&path_buf)?)];
   for shape_item in target_shape {
+    if let Some(name) = shape_item.name() {
+      let create_it = creation.create && creation.options.create_optional;
+      let path = path_buf.join(name);
+      if shape_item.options().optional && !create_it && !path.exists() {
+        if creation.create {
+          creation.emit(ShapeEvent::Skipped(path))?;
+        }
+        res.push(ShapeInstItem::Missing);
+        continue;
+      }
+    }
     match shape_item {
-      ShapeItemStatic::File(_, name) => {
+      ShapeItem::File(_, name, item_options) => {
         let file = File::new(path_buf.join(name))?;
-        if create {
-          file.create()?;
-          if let Some(hook_fn) = hook {
-            hook_fn(path_buf.join(name), true);
-          }
+        if creation.create {
+          creation.create_file(&file, item_options.content.as_deref())?;
+          creation.apply_mode(&file.path, &item_options.mode)?;
         }
         res.push(ShapeInstItem::File(file));
       }
-      ShapeItemStatic::DirectoryPattern(_, name, _) => {
+      ShapeItem::DirectoryPattern(_, name, _, item_options) => {
         let dir = Dir::new(path_buf.join(name))?;
-        if create {
-          dir.create()?;
-          if let Some(hook_fn) = hook {
-            hook_fn(path_buf.join(name), true);
-          }
+        if creation.create {
+          creation.create_dir(&dir)?;
+          creation.apply_mode(&dir.path, &item_options.mode)?;
         }
         res.push(ShapeInstItem::Directory(dir));
       }
-      ShapeItemStatic::DirectorySchema(_, name, schema) => {
+      ShapeItem::DirectorySchema(_, name, schema, item_options) => {
         let dir = Dir::new(path_buf.join(name))?;
-        if create {
-          dir.create()?;
-          if let Some(hook_fn) = hook {
-            hook_fn(path_buf.join(name), true);
-          }
+        if creation.create {
+          creation.create_dir(&dir)?;
+        }
+        let child = create_shape_inst(dir.path.clone(), schema, creation)?;
+        // applied after the children are created, a readonly folder can't get new entries
+        if creation.create {
+          creation.apply_mode(&dir.path, &item_options.mode)?;
         }
-        let child = create_shape_inst(path_buf.join(name), schema, create, hook)?;
         res.push(ShapeInstItem::ShapedDirectory(child));
       }
+      ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) => {
+        let mut children = vec![];
+        let dir = Dir::new(&path_buf)?;
+        if dir.exists() {
+          for sub_dir in matching_dirs(&dir, target_shape, &Pattern::new(pattern)?)? {
+            let name = sub_dir.name()?.to_string();
+            let child = create_shape_inst(sub_dir.path, schema, creation)?;
+            children.push((name, child));
+          }
+        }
+        res.push(ShapeInstItem::ShapedDirectories(children));
+      }
+      ShapeItem::Rest(_) => {}
+      ShapeItem::Variants(variants) => {
+        let dir = Dir::new(&path_buf)?;
+        let matching = match closest_variant(&dir, variants, "") {
+          (i, violations) if dir.exists() && violations.is_empty() => Some(i),
+          // the first variant is created when none matches
+          _ if creation.create => Some(0),
+          _ => None,
+        };
+        let (i, schema) = match matching.and_then(|i| Some((i, &variants.get(i)?.1))) {
+          Some(variant) => variant,
+          None => {
+            return Err(
+              Error::new(ErrorKind::InvalidFolder, "the folder matches no variant")
+                .set_path(path_buf),
+            )
+          }
+        };
+        let child = create_shape_inst(path_buf.clone(), schema, creation)?;
+        res.push(ShapeInstItem::Variant(i, child));
+      }
     }
   }
   Ok(res)
@@ -270,12 +1206,15 @@ fn create_shape_inst(
 
 impl std::fmt::Debug for ShapeItemStatic<'_> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.options().optional {
+      write!(f, "Optional(")?;
+    }
     match self {
-      ShapeItemStatic::File(_, name) => write!(f, "File('{}')", name),
-      ShapeItemStatic::DirectoryPattern(_, name, pattern) => {
+      ShapeItemStatic::File(_, name, _) => write!(f, "File('{}')", name),
+      ShapeItemStatic::DirectoryPattern(_, name, pattern, _) => {
         write!(f, "Dir('{}', FilePattern({}))", name, pattern)
       }
-      ShapeItemStatic::DirectorySchema(_, name, inner_schema) => {
+      ShapeItemStatic::DirectorySchema(_, name, inner_schema, _) => {
         write!(f, "Dir('{}',", name)?;
         if inner_schema.len() > 0 {
           let mut fo = f.debug_struct("");
@@ -288,7 +1227,30 @@ impl std::fmt::Debug for ShapeItemStatic<'_> {
         }
         write!(f, ")")
       }
+      ShapeItemStatic::DirectorySchemaPattern(_, pattern, inner_schema, _) => {
+        write!(f, "Dirs(DirPattern({}),", pattern)?;
+        let mut fo = f.debug_struct("");
+        for schema in *inner_schema {
+          fo.field(schema.identifier(), schema);
+        }
+        fo.finish()?;
+        write!(f, ")")
+      }
+      ShapeItemStatic::Rest(rest) => write!(f, "Rest({:?})", rest),
+      ShapeItemStatic::Variants(variants) => {
+        write!(f, "Variants(")?;
+        let mut fo = f.debug_struct("");
+        for (name, inner_schema) in *variants {
+          fo.field(name, inner_schema);
+        }
+        fo.finish()?;
+        write!(f, ")")
+      }
+    }?;
+    if self.options().optional {
+      write!(f, ")")?;
     }
+    Ok(())
   }
 }
 
@@ -0,0 +1,417 @@
+use super::{
+  create_at, invalid_schema_report, open, params, validate, validate_report, ContentValidator,
+  CreateOptions, Errors, Migration, RepairLog, RepairPolicy, ShapeContext, ShapeDiff, ShapeEvent,
+  ShapeHookResult, ShapeInst, ShapeInstItem, ShapeItem, ShapeItemOptions, ShapeRest, ShapeSchema,
+  ValidationReport,
+};
+use crate::error;
+use crate::{dir::Dir, file::File};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// builds a shape at runtime, without `#[derive(Shape)]`
+///
+/// every entry is identified by it's name (or pattern for `dirs`) unless
+/// `identifier` is called after adding it, names can have placeholders like
+/// the names of `#[derive(Shape)]` (see `Shape::create_at_with`)
+/// ```no_run
+/// # fn main() -> fs_pro::error::Result<()> {
+/// use fs_pro::shape::ShapeBuilder;
+///
+/// let child = ShapeBuilder::new().file("child.txt");
+/// let shape = ShapeBuilder::new()
+///   .file("a.txt")
+///   .content("hello")
+///   .dir_pattern("logs", "*.log")
+///   .dir("sub", child)
+///   .build();
+/// let shape_inst = shape.create_at("target")?;
+/// println!("{:?}", shape_inst["a.txt"].file()); // Some(File)
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShapeBuilder {
+  schema: ShapeSchema,
+}
+
+impl ShapeBuilder {
+  /// creates an empty shape builder
+  pub fn new() -> Self {
+    ShapeBuilder { schema: vec![] }
+  }
+  /// adds a file
+  pub fn file<N: Into<String>>(mut self, name: N) -> Self {
+    let name = name.into();
+    self.schema.push(ShapeItem::File(
+      name.clone(),
+      name,
+      ShapeItemOptions::DEFAULT,
+    ));
+    self
+  }
+  /// adds a directory that can only contain files matching the pattern
+  pub fn dir_pattern<N: Into<String>, S: Into<String>>(mut self, name: N, pattern: S) -> Self {
+    let name = name.into();
+    self.schema.push(ShapeItem::DirectoryPattern(
+      name.clone(),
+      name,
+      pattern.into(),
+      ShapeItemOptions::DEFAULT,
+    ));
+    self
+  }
+  /// adds a directory with the shape of child
+  pub fn dir<N: Into<String>>(mut self, name: N, child: ShapeBuilder) -> Self {
+    let name = name.into();
+    self.schema.push(ShapeItem::DirectorySchema(
+      name.clone(),
+      name,
+      child.schema,
+      ShapeItemOptions::DEFAULT,
+    ));
+    self
+  }
+  /// adds every directory which name matches the pattern, each of them must have the shape of child
+  pub fn dirs<S: Into<String>>(mut self, pattern: S, child: ShapeBuilder) -> Self {
+    let pattern = pattern.into();
+    self.schema.push(ShapeItem::DirectorySchemaPattern(
+      pattern.clone(),
+      pattern,
+      child.schema,
+      ShapeItemOptions::DEFAULT,
+    ));
+    self
+  }
+  /// sets how undeclared entries are treated (see `ShapeRest`)
+  pub fn rest(mut self, rest: ShapeRest<'static>) -> Self {
+    self
+      .schema
+      .retain(|item| !matches!(item, ShapeItem::Rest(_)));
+    self.schema.push(ShapeItem::Rest(rest));
+    self
+  }
+  /// adds a variant, the folder must match one of the variants (like a `#[derive(Shape)]`
+  /// enum), see `Shape::validate`
+  /// ```
+  /// use fs_pro::shape::ShapeBuilder;
+  ///
+  /// let shape = ShapeBuilder::new()
+  ///   .variant("python", ShapeBuilder::new().file("setup.py"))
+  ///   .variant("rust", ShapeBuilder::new().file("Cargo.toml"))
+  ///   .build();
+  /// ```
+  pub fn variant<N: Into<String>>(mut self, name: N, child: ShapeBuilder) -> Self {
+    let variant = (name.into(), child.schema);
+    let variants = self.schema.iter_mut().find_map(|item| match item {
+      ShapeItem::Variants(variants) => Some(variants),
+      _ => None,
+    });
+    match variants {
+      Some(variants) => variants.push(variant),
+      None => self.schema.push(ShapeItem::Variants(vec![variant])),
+    }
+    self
+  }
+  /// sets the identifier of the last added entry
+  pub fn identifier<S: Into<String>>(mut self, identifier: S) -> Self {
+    match self.schema.last_mut() {
+      Some(ShapeItem::File(id, _, _))
+      | Some(ShapeItem::DirectoryPattern(id, _, _, _))
+      | Some(ShapeItem::DirectorySchema(id, _, _, _))
+      | Some(ShapeItem::DirectorySchemaPattern(id, _, _, _)) => *id = identifier.into(),
+      _ => {}
+    }
+    self
+  }
+  /// makes the last added entry optional
+  pub fn optional(mut self) -> Self {
+    if let Some(options) = self.last_options() {
+      options.optional = true;
+    }
+    self
+  }
+  /// sets the initial content of the last added entry (only used with files)
+  pub fn content<C: Into<Vec<u8>>>(mut self, content: C) -> Self {
+    if let Some(options) = self.last_options() {
+      options.content = Some(Cow::Owned(content.into()));
+    }
+    self
+  }
+  /// sets the exact permissions of the last added entry, see `ModeRequirement`
+  pub fn mode(mut self, mode: u32) -> Self {
+    if let Some(options) = self.last_options() {
+      options.mode.mode = Some(mode);
+    }
+    self
+  }
+  /// makes the last added entry executable, see `ModeRequirement`
+  pub fn executable(mut self) -> Self {
+    if let Some(options) = self.last_options() {
+      options.mode.executable = true;
+    }
+    self
+  }
+  /// makes the last added entry readonly, see `ModeRequirement`
+  pub fn readonly(mut self) -> Self {
+    if let Some(options) = self.last_options() {
+      options.mode.readonly = true;
+    }
+    self
+  }
+  /// adds a check of the content of the last added file, see `ContentValidator`
+  /// ```
+  /// use fs_pro::shape::{ContentValidator, ShapeBuilder};
+  ///
+  /// let shape = ShapeBuilder::new()
+  ///   .file("config.json")
+  ///   .validator(ContentValidator::NonEmpty)
+  ///   .validator(ContentValidator::MaxSize(4096))
+  ///   .build();
+  /// ```
+  pub fn validator(mut self, validator: ContentValidator) -> Self {
+    if let Some(ShapeItem::File(_, _, options)) = self.schema.last_mut() {
+      options.validators.to_mut().push(validator);
+    }
+    self
+  }
+  fn last_options(&mut self) -> Option<&mut ShapeItemOptions<'static>> {
+    match self.schema.last_mut() {
+      Some(ShapeItem::File(_, _, options))
+      | Some(ShapeItem::DirectoryPattern(_, _, _, options))
+      | Some(ShapeItem::DirectorySchema(_, _, _, options))
+      | Some(ShapeItem::DirectorySchemaPattern(_, _, _, options)) => Some(options),
+      _ => None,
+    }
+  }
+  /// returns the built schema
+  pub fn schema(self) -> ShapeSchema {
+    self.schema
+  }
+  /// builds the shape
+  pub fn build(self) -> DynShape {
+    DynShape::new(self.schema)
+  }
+}
+
+/// a shape defined at runtime, it's the runtime version of `Shape`
+///
+/// create it with `ShapeBuilder` or from an owned `ShapeSchema`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynShape {
+  schema: ShapeSchema,
+}
+
+impl DynShape {
+  /// creates a shape from a schema
+  pub fn new(schema: ShapeSchema) -> Self {
+    DynShape { schema }
+  }
+  /// infers a shape from an existing directory
+  ///
+  /// files become files, folders that are empty or have only files with the same
+  /// extension become pattern dirs (like `#[pattern = "*.log"]`) and other folders
+  /// become nested shapes, identifiers are the names converted to valid field names
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::{shape::DynShape, Dir};
+  ///
+  /// let shape = DynShape::infer(&Dir::new("legacy_layout")?)?;
+  /// println!("{}", shape.to_rust("LegacyLayout")?);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn infer(dir: &Dir) -> error::Result<Self> {
+    // names on disk are used as they are, even with braces
+    Ok(DynShape::new(params::escape(&super::infer::infer(dir)?)))
+  }
+  /// returns the schema of the shape
+  pub fn schema(&self) -> &ShapeSchema {
+    &self.schema
+  }
+  /// generates the source of `#[derive(Shape)]` structs for the shape, the root
+  /// struct is named name and nested shapes are named after their field
+  ///
+  /// fails if a name or pattern can't be written in an attribute (contains `/` or a name
+  /// has a malformed placeholder),
+  /// `ContentValidator::With` checks are left out since the path of the function isn't known
+  pub fn to_rust(&self, name: &str) -> error::Result<String> {
+    super::infer::to_rust(&self.schema, name)
+  }
+  /// create the shape in a directory, see `Shape::create_at`
+  pub fn create_at<P: AsRef<Path>>(&self, path: P) -> error::Result<DynShapeInst> {
+    self.create_at_with_options(path, &CreateOptions::new())
+  }
+  /// like `create_at` but with options
+  pub fn create_at_with_options<P: AsRef<Path>>(
+    &self,
+    path: P,
+    options: &CreateOptions,
+  ) -> error::Result<DynShapeInst> {
+    self.create_at_hook(path, options, |_| ShapeHookResult::Continue)
+  }
+  /// like `create_at_with_options` but calls hook with an event for every entry,
+  /// see `Shape::create_at_hook`
+  pub fn create_at_hook<P, F>(
+    &self,
+    path: P,
+    options: &CreateOptions,
+    mut hook: F,
+  ) -> error::Result<DynShapeInst>
+  where
+    P: AsRef<Path>,
+    F: FnMut(ShapeEvent) -> ShapeHookResult,
+  {
+    let schema = params::literal(&self.schema)?;
+    let res = create_at(path, &schema, options, &mut hook)?;
+    Ok(to_dyn_inst(&schema, res))
+  }
+  /// the shape with the placeholders in the names of it's entries filled from ctx,
+  /// see `Shape::create_at_with`
+  pub fn resolve(&self, ctx: &ShapeContext) -> error::Result<DynShape> {
+    // the filled names are escaped so the other methods use them as they are
+    let schema = params::resolve(&self.schema, ctx)?;
+    Ok(DynShape::new(params::escape(&schema)))
+  }
+  /// like `create_at` but fills the placeholders in names from ctx,
+  /// see `Shape::create_at_with`
+  pub fn create_at_with<P: AsRef<Path>>(
+    &self,
+    path: P,
+    ctx: &ShapeContext,
+  ) -> error::Result<DynShapeInst> {
+    self.resolve(ctx)?.create_at(path)
+  }
+  /// like `validate` but fills the placeholders in names from ctx
+  pub fn validate_with<P: AsRef<Path>>(&self, path: P, ctx: &ShapeContext) -> Result<(), Errors> {
+    self.resolve(ctx).map_err(|e| vec![e])?.validate(path)
+  }
+  /// binds an existing directory to the shape without creating anything
+  /// see `Shape::open`
+  pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<DynShapeInst, Errors> {
+    let schema = params::literal(&self.schema).map_err(|e| vec![e])?;
+    let res = open(path, &schema)?;
+    Ok(to_dyn_inst(&schema, res))
+  }
+  /// checks if a folder matches the shape, see `Shape::validate`
+  pub fn validate<P: AsRef<Path>>(&self, path: P) -> Result<(), Errors> {
+    let schema = params::literal(&self.schema).map_err(|e| vec![e])?;
+    validate(path, &schema)
+  }
+  /// fixes a directory to match the shape, see `Shape::repair`
+  pub fn repair<P: AsRef<Path>>(&self, path: P, policy: &RepairPolicy) -> error::Result<RepairLog> {
+    super::repair::repair(path, &params::literal(&self.schema)?, policy)
+  }
+  /// checks if a folder matches the shape, see `Shape::validate_report`
+  pub fn validate_report<P: AsRef<Path>>(&self, path: P) -> ValidationReport {
+    match params::literal(&self.schema) {
+      Ok(schema) => validate_report(path, &schema),
+      Err(e) => invalid_schema_report(path.as_ref(), e),
+    }
+  }
+  /// runs a migration on a directory and checks it matches the shape, see `Shape::migrate`
+  /// (including where the backup of deleted entries is kept)
+  pub fn migrate<P: AsRef<Path>>(
+    &self,
+    path: P,
+    migration: &Migration,
+  ) -> error::Result<DynShapeInst> {
+    let schema = params::literal(&self.schema)?;
+    let res = super::migrate::migrate(path, migration, &schema)?;
+    Ok(to_dyn_inst(&schema, res))
+  }
+  /// the structural difference between the shape and a directory, see `ShapeDiff::with_dir`
+  pub fn diff_dir<P: AsRef<Path>>(&self, path: P) -> error::Result<ShapeDiff> {
+    ShapeDiff::with_dir(&params::literal(&self.schema)?, path)
+  }
+}
+
+/// an entry of a `DynShapeInst`
+#[derive(Debug)]
+#[allow(missing_docs)]
+pub enum DynShapeInstItem {
+  File(File),
+  Directory(Dir),
+  ShapedDirectory(DynShapeInst),
+  /// every folder matching the pattern with it's name, sorted by name
+  ShapedDirectories(Vec<(String, DynShapeInst)>),
+  /// an optional entry that doesn't exist
+  Missing,
+  /// the name of the variant the folder matches with it's entries
+  Variant(String, DynShapeInst),
+}
+
+impl DynShapeInstItem {
+  /// returns the file if the entry is a file
+  pub fn file(&self) -> Option<&File> {
+    if let DynShapeInstItem::File(file) = self {
+      Some(file)
+    } else {
+      None
+    }
+  }
+  /// returns the directory if the entry is a pattern directory
+  pub fn dir(&self) -> Option<&Dir> {
+    if let DynShapeInstItem::Directory(dir) = self {
+      Some(dir)
+    } else {
+      None
+    }
+  }
+  /// returns the instance of the child shape if the entry is a shaped directory
+  pub fn shape(&self) -> Option<&DynShapeInst> {
+    if let DynShapeInstItem::ShapedDirectory(inst) = self {
+      Some(inst)
+    } else {
+      None
+    }
+  }
+  /// returns the name of the matching variant and it's instance if the entry is the
+  /// variants of the folder (it's identifier is `__variants`)
+  pub fn variant(&self) -> Option<(&str, &DynShapeInst)> {
+    if let DynShapeInstItem::Variant(name, inst) = self {
+      Some((name, inst))
+    } else {
+      None
+    }
+  }
+}
+
+/// an instance of a `DynShape` keyed by identifier
+pub type DynShapeInst = HashMap<String, DynShapeInstItem>;
+
+fn to_dyn_inst(schema: &[ShapeItem], inst: ShapeInst) -> DynShapeInst {
+  let mut result = HashMap::new();
+  let items = schema
+    .iter()
+    .filter(|item| !matches!(item, ShapeItem::Rest(_)));
+  let inst_items = inst
+    .into_iter()
+    .filter(|item| !matches!(item, ShapeInstItem::Root(_)));
+  for (item, inst_item) in items.zip(inst_items) {
+    let dyn_item = match (item, inst_item) {
+      (_, ShapeInstItem::File(file)) => DynShapeInstItem::File(file),
+      (_, ShapeInstItem::Directory(dir)) => DynShapeInstItem::Directory(dir),
+      (ShapeItem::DirectorySchema(_, _, child, _), ShapeInstItem::ShapedDirectory(inst)) => {
+        DynShapeInstItem::ShapedDirectory(to_dyn_inst(child, inst))
+      }
+      (
+        ShapeItem::DirectorySchemaPattern(_, _, child, _),
+        ShapeInstItem::ShapedDirectories(insts),
+      ) => DynShapeInstItem::ShapedDirectories(
+        insts
+          .into_iter()
+          .map(|(name, inst)| (name, to_dyn_inst(child, inst)))
+          .collect(),
+      ),
+      (ShapeItem::Variants(variants), ShapeInstItem::Variant(i, inst)) => {
+        let (name, child) = &variants[i];
+        DynShapeInstItem::Variant(name.clone(), to_dyn_inst(child, inst))
+      }
+      _ => DynShapeInstItem::Missing,
+    };
+    result.insert(item.identifier().to_string(), dyn_item);
+  }
+  result
+}
@@ -0,0 +1,626 @@
+use super::{
+  child_identifier, closest_variant, is_named, matching_dirs, params, schema_from_static,
+  schema_patterns, EntryType, ShapeDescribe, ShapeItem, ShapeRest, ShapeSchema,
+};
+use crate::dir::Dir;
+use crate::error;
+use crate::pattern::Pattern;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// how an entry differs between two schemas, or between a schema and a directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+  /// the entry only exists in the new schema (or only in the directory)
+  Added {
+    /// the type of the entry
+    entry_type: EntryType,
+  },
+  /// the entry only exists in the old schema (or is missing from the directory)
+  Removed {
+    /// the type of the entry
+    entry_type: EntryType,
+  },
+  /// the entry kept it's identifier but got a new name
+  Renamed {
+    /// the old path of the entry
+    from: PathBuf,
+  },
+  /// a file became a folder or the opposite
+  Retyped {
+    /// the old type
+    from: EntryType,
+    /// the new type
+    to: EntryType,
+  },
+  /// the pattern of a pattern dir or of a `Vec` / `HashMap` field changed,
+  /// None if the folder is (or was) a nested shape
+  PatternChanged {
+    /// the old pattern
+    from: Option<String>,
+    /// the new pattern
+    to: Option<String>,
+  },
+  /// the entry became optional or required
+  OptionalChanged {
+    /// true if the entry is optional in the new schema
+    optional: bool,
+  },
+  /// the rest of a folder changed
+  RestChanged {
+    /// the old rest
+    from: ShapeRest<'static>,
+    /// the new rest
+    to: ShapeRest<'static>,
+  },
+}
+
+/// a single difference, see `ShapeDiff`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeChange {
+  /// what changed
+  pub kind: ChangeKind,
+  /// the path of the entry relative to the root, entries of `Vec` and `HashMap`
+  /// fields are under their pattern (like `tenant-*/config.toml`) in schema diffs
+  pub path: PathBuf,
+  /// the identifier of the entry (in the new schema if it exists there), like `hi_dir.hi`
+  pub identifier: String,
+}
+
+fn rest_str(rest: &ShapeRest<'_>) -> String {
+  match rest {
+    ShapeRest::Allow => "allow".to_string(),
+    ShapeRest::Forbid => "forbid".to_string(),
+    ShapeRest::Pattern(pattern) => format!("pattern \"{}\"", pattern),
+  }
+}
+
+impl ShapeChange {
+  fn new<S: Into<String>>(kind: ChangeKind, path: PathBuf, identifier: S) -> Self {
+    ShapeChange {
+      kind,
+      path,
+      identifier: identifier.into(),
+    }
+  }
+  /// a short human readable description of the change
+  pub fn message(&self) -> String {
+    match &self.kind {
+      ChangeKind::Added { entry_type } => format!("added {}", entry_type.as_str()),
+      ChangeKind::Removed { entry_type } => format!("removed {}", entry_type.as_str()),
+      ChangeKind::Renamed { from } => format!("renamed from {}", from.display()),
+      ChangeKind::Retyped { from, to } => {
+        format!("changed from a {} to a {}", from.as_str(), to.as_str())
+      }
+      ChangeKind::PatternChanged { from, to } => {
+        let pattern = |pattern: &Option<String>| match pattern {
+          Some(pattern) => format!("\"{}\"", pattern),
+          None => "a nested shape".to_string(),
+        };
+        format!("pattern changed from {} to {}", pattern(from), pattern(to))
+      }
+      ChangeKind::OptionalChanged { optional: true } => "became optional".to_string(),
+      ChangeKind::OptionalChanged { optional: false } => "became required".to_string(),
+      ChangeKind::RestChanged { from, to } => {
+        format!("rest changed from {} to {}", rest_str(from), rest_str(to))
+      }
+    }
+  }
+  #[cfg(feature = "json")]
+  fn kind_name(&self) -> &'static str {
+    match self.kind {
+      ChangeKind::Added { .. } => "added",
+      ChangeKind::Removed { .. } => "removed",
+      ChangeKind::Renamed { .. } => "renamed",
+      ChangeKind::Retyped { .. } => "retyped",
+      ChangeKind::PatternChanged { .. } => "pattern_changed",
+      ChangeKind::OptionalChanged { .. } => "optional_changed",
+      ChangeKind::RestChanged { .. } => "rest_changed",
+    }
+  }
+}
+
+/// the structural difference between two schemas or between a schema and a directory
+/// ```no_run
+/// # use fs_pro::{File, Shape};
+/// # #[derive(Shape)]
+/// # struct OldLayout {
+/// #   #[name = "config.toml"]
+/// #   pub config: File,
+/// # }
+/// # #[derive(Shape)]
+/// # struct NewLayout {
+/// #   #[name = "settings.toml"]
+/// #   pub config: File,
+/// # }
+/// # fn main() -> fs_pro::error::Result<()> {
+/// use fs_pro::shape::ShapeDiff;
+///
+/// let diff = ShapeDiff::between_types::<OldLayout, NewLayout>()?;
+/// for change in &diff.changes {
+///   println!("{}: {}", change.path.display(), change.message());
+/// }
+/// # let shape: Shape<NewLayout> = Shape::new();
+/// let drift = shape.diff_dir("target")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ShapeDiff {
+  /// every change, in the order of the schemas
+  pub changes: Vec<ShapeChange>,
+}
+
+fn entry_type(item: &ShapeItem) -> EntryType {
+  match item {
+    ShapeItem::File(_, _, _) => EntryType::File,
+    _ => EntryType::Dir,
+  }
+}
+
+// the path of an entry, Vec and HashMap fields are written as their pattern
+fn item_path(parent: &Path, item: &ShapeItem) -> PathBuf {
+  match item {
+    ShapeItem::DirectorySchemaPattern(_, pattern, _, _) => parent.join(pattern),
+    _ => parent.join(item.name().unwrap_or_default()),
+  }
+}
+
+fn rest_of(schema: &[ShapeItem]) -> ShapeRest<'static> {
+  for item in schema {
+    if let ShapeItem::Rest(rest) = item {
+      return rest.clone();
+    }
+  }
+  ShapeRest::Allow
+}
+
+// the variants of an enum schema
+fn variants_of(schema: &[ShapeItem]) -> &[(String, ShapeSchema)] {
+  for item in schema {
+    if let ShapeItem::Variants(variants) = item {
+      return variants;
+    }
+  }
+  &[]
+}
+
+// rest and variants are compared on their own
+fn is_entry(item: &ShapeItem) -> bool {
+  !matches!(item, ShapeItem::Rest(_) | ShapeItem::Variants(_))
+}
+
+fn same_entry(old: &ShapeItem, new: &ShapeItem) -> bool {
+  match (old, new) {
+    (
+      ShapeItem::DirectorySchemaPattern(_, a, _, _),
+      ShapeItem::DirectorySchemaPattern(_, b, _, _),
+    ) => a == b,
+    _ => old.name().is_some() && old.name() == new.name(),
+  }
+}
+
+fn same_identifier(old: &ShapeItem, new: &ShapeItem) -> bool {
+  let is_dirs = |item: &ShapeItem| matches!(item, ShapeItem::DirectorySchemaPattern(_, _, _, _));
+  old.identifier() == new.identifier() && is_dirs(old) == is_dirs(new)
+}
+
+impl ShapeDiff {
+  /// returns true if there are no changes
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+  /// the difference between the schemas of two `#[derive(Shape)]` structs
+  ///
+  /// the names are the ones used on disk (`{{` and `}}` are literal braces, like in
+  /// `Shape::diff_dir`), it fails with `InvalidSchema` if a name has a placeholder
+  pub fn between_types<Old: ShapeDescribe, New: ShapeDescribe>() -> error::Result<Self> {
+    Ok(ShapeDiff::between(
+      &params::literal(&schema_from_static(Old::shape_describe()))?,
+      &params::literal(&schema_from_static(New::shape_describe()))?,
+    ))
+  }
+  /// the difference between two schemas
+  ///
+  /// entries are matched by name (by pattern for `Vec` and `HashMap` fields),
+  /// entries that didn't match are then matched by identifier, these were renamed
+  /// (or had their pattern changed)
+  pub fn between(old: &[ShapeItem], new: &[ShapeItem]) -> Self {
+    let mut diff = ShapeDiff::default();
+    diff.schemas(old, new, Path::new(""), "", "");
+    diff
+  }
+  /// the difference between a schema and a directory, declared entries that are missing
+  /// are `Removed` (unless they are optional), undeclared entries and files that don't
+  /// match the pattern of their folder are `Added` (even if the rest allows them) and
+  /// entries of the wrong type are `Retyped` from the declared type to the found one
+  pub fn with_dir<P: AsRef<Path>>(schema: &[ShapeItem], path: P) -> error::Result<Self> {
+    let mut diff = ShapeDiff::default();
+    let dir = Dir::new(path)?;
+    diff.dir(&dir, Path::new(""), schema, "")?;
+    Ok(diff)
+  }
+  /// renders the diff as json
+  /// ```json
+  /// {
+  ///   "changes": [
+  ///     {
+  ///       "kind": "renamed",
+  ///       "path": "settings.toml",
+  ///       "identifier": "config",
+  ///       "message": "renamed from config.toml",
+  ///       "from": "config.toml"
+  ///     }
+  ///   ]
+  /// }
+  /// ```
+  /// `added` and `removed` changes also have `entry_type`, `retyped`, `pattern_changed`
+  /// and `rest_changed` have `from` and `to` and `optional_changed` has `optional`
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> String {
+    use serde_json::{json, Value};
+
+    let changes: Vec<Value> = self
+      .changes
+      .iter()
+      .map(|change| {
+        let mut value = json!({
+          "kind": change.kind_name(),
+          "path": change.path.to_string_lossy(),
+          "identifier": change.identifier,
+          "message": change.message(),
+        });
+        let extra = match &change.kind {
+          ChangeKind::Added { entry_type } | ChangeKind::Removed { entry_type } => {
+            json!({ "entry_type": entry_type.as_str() })
+          }
+          ChangeKind::Renamed { from } => json!({ "from": from.to_string_lossy() }),
+          ChangeKind::Retyped { from, to } => {
+            json!({ "from": from.as_str(), "to": to.as_str() })
+          }
+          ChangeKind::PatternChanged { from, to } => json!({ "from": from, "to": to }),
+          ChangeKind::OptionalChanged { optional } => json!({ "optional": optional }),
+          ChangeKind::RestChanged { from, to } => {
+            json!({ "from": serde_json::to_value(from).unwrap(), "to": serde_json::to_value(to).unwrap() })
+          }
+        };
+        if let (Value::Object(value), Value::Object(extra)) = (&mut value, extra) {
+          value.extend(extra);
+        }
+        value
+      })
+      .collect();
+    serde_json::to_string_pretty(&json!({ "changes": changes })).unwrap()
+  }
+  fn push(&mut self, kind: ChangeKind, path: PathBuf, identifier: &str) {
+    self.changes.push(ShapeChange::new(kind, path, identifier));
+  }
+  // adds every entry of schema as Added or Removed
+  fn all(&mut self, schema: &[ShapeItem], parent: &Path, parent_id: &str, added: bool) {
+    let kind = |entry_type| {
+      if added {
+        ChangeKind::Added { entry_type }
+      } else {
+        ChangeKind::Removed { entry_type }
+      }
+    };
+    for item in schema.iter().filter(|item| is_entry(item)) {
+      let identifier = child_identifier(parent_id, item.identifier());
+      self.push(kind(entry_type(item)), item_path(parent, item), &identifier);
+    }
+    for (name, _) in variants_of(schema) {
+      let identifier = child_identifier(parent_id, name);
+      self.push(kind(EntryType::Dir), parent.to_path_buf(), &identifier);
+    }
+  }
+  fn schemas(
+    &mut self,
+    old: &[ShapeItem],
+    new: &[ShapeItem],
+    parent: &Path,
+    old_parent_id: &str,
+    parent_id: &str,
+  ) {
+    let (old_rest, new_rest) = (rest_of(old), rest_of(new));
+    if old_rest != new_rest {
+      let kind = ChangeKind::RestChanged {
+        from: old_rest,
+        to: new_rest,
+      };
+      self.push(
+        kind,
+        parent.to_path_buf(),
+        &child_identifier(parent_id, "__rest"),
+      );
+    }
+    let mut unmatched_old: Vec<Option<&ShapeItem>> =
+      old.iter().filter(|item| is_entry(item)).map(Some).collect();
+    let mut unmatched_new = vec![];
+    for item in new.iter().filter(|item| is_entry(item)) {
+      let position = unmatched_old
+        .iter()
+        .position(|old| old.map(|old| same_entry(old, item)).unwrap_or(false));
+      match position {
+        Some(i) => {
+          let old_item = unmatched_old[i].take().unwrap();
+          self.entries(old_item, item, parent, old_parent_id, parent_id);
+        }
+        None => unmatched_new.push(item),
+      }
+    }
+    let mut added = vec![];
+    for item in unmatched_new {
+      let position = unmatched_old
+        .iter()
+        .position(|old| old.map(|old| same_identifier(old, item)).unwrap_or(false));
+      match position {
+        Some(i) => {
+          let old_item = unmatched_old[i].take().unwrap();
+          self.entries(old_item, item, parent, old_parent_id, parent_id);
+        }
+        None => added.push(item),
+      }
+    }
+    for old_item in unmatched_old.into_iter().flatten() {
+      let identifier = child_identifier(old_parent_id, old_item.identifier());
+      let entry_type = entry_type(old_item);
+      self.push(
+        ChangeKind::Removed { entry_type },
+        item_path(parent, old_item),
+        &identifier,
+      );
+    }
+    for item in added {
+      let identifier = child_identifier(parent_id, item.identifier());
+      let entry_type = entry_type(item);
+      self.push(
+        ChangeKind::Added { entry_type },
+        item_path(parent, item),
+        &identifier,
+      );
+    }
+    self.variants(old, new, parent, old_parent_id, parent_id);
+  }
+  // variants are matched by name and their schemas compared, the others are
+  // added or removed folders with the name of the variant as identifier
+  fn variants(
+    &mut self,
+    old: &[ShapeItem],
+    new: &[ShapeItem],
+    parent: &Path,
+    old_parent_id: &str,
+    parent_id: &str,
+  ) {
+    let (old, new) = (variants_of(old), variants_of(new));
+    for (name, schema) in new {
+      let identifier = child_identifier(parent_id, name);
+      match old.iter().find(|(old_name, _)| old_name == name) {
+        Some((_, old_schema)) => {
+          let old_identifier = child_identifier(old_parent_id, name);
+          self.schemas(old_schema, schema, parent, &old_identifier, &identifier);
+        }
+        None => {
+          let kind = ChangeKind::Added {
+            entry_type: EntryType::Dir,
+          };
+          self.push(kind, parent.to_path_buf(), &identifier);
+        }
+      }
+    }
+    for (name, _) in old {
+      if !new.iter().any(|(new_name, _)| new_name == name) {
+        let kind = ChangeKind::Removed {
+          entry_type: EntryType::Dir,
+        };
+        self.push(
+          kind,
+          parent.to_path_buf(),
+          &child_identifier(old_parent_id, name),
+        );
+      }
+    }
+  }
+  fn entries(
+    &mut self,
+    old: &ShapeItem,
+    new: &ShapeItem,
+    parent: &Path,
+    old_parent_id: &str,
+    parent_id: &str,
+  ) {
+    let path = item_path(parent, new);
+    let old_path = item_path(parent, old);
+    let identifier = child_identifier(parent_id, new.identifier());
+    let old_identifier = child_identifier(old_parent_id, old.identifier());
+    if old.name().is_some() && old_path != path {
+      self.push(
+        ChangeKind::Renamed { from: old_path },
+        path.clone(),
+        &identifier,
+      );
+    }
+    let (old_optional, optional) = (old.options().optional, new.options().optional);
+    if old_optional != optional {
+      self.push(
+        ChangeKind::OptionalChanged { optional },
+        path.clone(),
+        &identifier,
+      );
+    }
+    match (old, new) {
+      (ShapeItem::File(_, _, _), ShapeItem::File(_, _, _)) => {}
+      (ShapeItem::File(_, _, _), _) | (_, ShapeItem::File(_, _, _)) => {
+        let kind = ChangeKind::Retyped {
+          from: entry_type(old),
+          to: entry_type(new),
+        };
+        self.push(kind, path, &identifier);
+      }
+      (ShapeItem::DirectoryPattern(_, _, from, _), ShapeItem::DirectoryPattern(_, _, to, _))
+        if from != to =>
+      {
+        let kind = ChangeKind::PatternChanged {
+          from: Some(from.clone()),
+          to: Some(to.clone()),
+        };
+        self.push(kind, path, &identifier);
+      }
+      (ShapeItem::DirectoryPattern(_, _, from, _), ShapeItem::DirectorySchema(_, _, schema, _)) => {
+        let kind = ChangeKind::PatternChanged {
+          from: Some(from.clone()),
+          to: None,
+        };
+        self.push(kind, path.clone(), &identifier);
+        self.all(schema, &path, &identifier, true);
+      }
+      (ShapeItem::DirectorySchema(_, _, schema, _), ShapeItem::DirectoryPattern(_, _, to, _)) => {
+        let kind = ChangeKind::PatternChanged {
+          from: None,
+          to: Some(to.clone()),
+        };
+        self.push(kind, path.clone(), &identifier);
+        self.all(schema, &path, &old_identifier, false);
+      }
+      (ShapeItem::DirectorySchema(_, _, a, _), ShapeItem::DirectorySchema(_, _, b, _)) => {
+        self.schemas(a, b, &path, &old_identifier, &identifier);
+      }
+      (
+        ShapeItem::DirectorySchemaPattern(_, from, a, _),
+        ShapeItem::DirectorySchemaPattern(_, to, b, _),
+      ) => {
+        if from != to {
+          let kind = ChangeKind::PatternChanged {
+            from: Some(from.clone()),
+            to: Some(to.clone()),
+          };
+          self.push(kind, path.clone(), &identifier);
+        }
+        self.schemas(a, b, &path, &old_identifier, &identifier);
+      }
+      _ => {}
+    }
+  }
+  fn dir(
+    &mut self,
+    dir: &Dir,
+    parent: &Path,
+    target: &[ShapeItem],
+    parent_id: &str,
+  ) -> error::Result<()> {
+    // the folder is compared to the variant it's closest to
+    let flattened: ShapeSchema;
+    let target = match variants_of(target) {
+      [] => target,
+      variants => {
+        let (i, _) = closest_variant(dir, variants, parent_id);
+        flattened = target
+          .iter()
+          .filter(|item| !matches!(item, ShapeItem::Variants(_)))
+          .chain(variants[i].1.iter())
+          .cloned()
+          .collect();
+        &flattened
+      }
+    };
+    for item in target {
+      let identifier = child_identifier(parent_id, item.identifier());
+      let path = item_path(parent, item);
+      let expected = entry_type(item);
+      if let Some(name) = item.name() {
+        match EntryType::of(dir.path.join(name)) {
+          None if item.options().optional => continue,
+          None => {
+            self.push(
+              ChangeKind::Removed {
+                entry_type: expected,
+              },
+              path,
+              &identifier,
+            );
+            continue;
+          }
+          Some(found) if found != expected => {
+            let kind = ChangeKind::Retyped {
+              from: expected,
+              to: found,
+            };
+            self.push(kind, path, &identifier);
+            continue;
+          }
+          Some(_) => {}
+        }
+      }
+      match item {
+        ShapeItem::DirectoryPattern(_, name, pattern, _) => {
+          let compiled = Pattern::new(pattern)?;
+          let mut entries = dir.get_dir(name)?.read()?;
+          entries.sort_by_key(|entry| entry.path());
+          for entry in entries {
+            let entry_type = if entry.is_dir() {
+              EntryType::Dir
+            } else {
+              EntryType::File
+            };
+            if entry_type == EntryType::Dir || !compiled.is_match(entry.file_name()) {
+              let entry_path = path.join(entry.file_name());
+              self.push(ChangeKind::Added { entry_type }, entry_path, &identifier);
+            }
+          }
+        }
+        ShapeItem::DirectorySchema(_, name, schema, _) => {
+          self.dir(&dir.get_dir(name)?, &path, schema, &identifier)?;
+        }
+        ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) => {
+          for sub_dir in matching_dirs(dir, target, &Pattern::new(pattern)?)? {
+            let name = sub_dir.name()?.to_string();
+            let sub_identifier = format!("{}[{}]", identifier, name);
+            self.dir(&sub_dir, &parent.join(&name), schema, &sub_identifier)?;
+          }
+        }
+        _ => {}
+      }
+    }
+    let patterns = schema_patterns(target)?;
+    let mut entries = dir.read()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+      let file_name = entry.file_name();
+      let declared = is_named(target, &entry)
+        || (entry.is_dir() && patterns.iter().any(|pattern| pattern.is_match(&file_name)));
+      if declared {
+        continue;
+      }
+      let entry_type = if entry.is_dir() {
+        EntryType::Dir
+      } else {
+        EntryType::File
+      };
+      let identifier = child_identifier(parent_id, "__rest");
+      self.push(
+        ChangeKind::Added { entry_type },
+        parent.join(file_name),
+        &identifier,
+      );
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for ShapeChange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.path.display(), self.message())?;
+    if !self.identifier.is_empty() {
+      write!(f, " [{}]", self.identifier)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for ShapeDiff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for change in &self.changes {
+      writeln!(f, "{}", change)?;
+    }
+    Ok(())
+  }
+}
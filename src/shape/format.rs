@@ -0,0 +1,579 @@
+use super::{
+  ContentValidator, DynShape, ModeRequirement, ShapeDescribe, ShapeItem, ShapeItemOptions,
+  ShapeRest, ShapeSchema,
+};
+use crate::error::{self, Error, ErrorKind};
+use crate::pattern::Pattern;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+use std::path::Path;
+
+/// a shape schema that can be read from and written to json, yaml or toml
+///
+/// example (yaml):
+/// ```
+/// # #[cfg(feature = "yaml")]
+/// # fn main() -> fs_pro::error::Result<()> {
+/// use fs_pro::shape::{ShapeDef, ShapeRest};
+///
+/// let def = ShapeDef::from_yaml(
+///   r#"
+/// rest: forbid
+/// entries:
+///   - type: file
+///     name: config.toml
+///     content: "name = \"app\"\n"
+///   - type: dir
+///     name: logs
+///     pattern: "*.log"
+///   - type: dir
+///     name: data
+///     optional: true
+///     rest: { pattern: "*.bak" }
+///     entries:
+///       - type: file
+///         name: db.sqlite
+///   - type: dirs
+///     pattern: "tenant-*"
+///     entries: []
+/// "#,
+/// )?;
+/// assert_eq!(def.rest, Some(ShapeRest::Forbid));
+/// assert_eq!(def.entries.len(), 4);
+/// def.build()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "yaml"))]
+/// # fn main() {}
+/// ```
+/// every entry can have an `identifier` (defaults to it's name or pattern),
+/// which is the key of the entry in a `DynShapeInst`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ShapeDef {
+  /// how undeclared entries are treated, `allow` (the default), `forbid`
+  /// or a pattern, written `{ pattern: "*.md" }` in yaml and `{ pattern = "*.md" }` in toml
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rest: Option<ShapeRest<'static>>,
+  /// the entries of the directory
+  #[serde(default)]
+  pub entries: Vec<ShapeEntryDef>,
+}
+
+/// an entry of a `ShapeDef`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeEntryDef {
+  /// a file
+  File {
+    /// the name of the file
+    name: String,
+    /// the key of the entry, defaults to the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+    /// the file may be missing
+    #[serde(default, skip_serializing_if = "is_false")]
+    optional: bool,
+    /// the initial content of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    /// the maximum size of the file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_size: Option<u64>,
+    /// the minimum size of the file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_size: Option<u64>,
+    /// the file can't be empty
+    #[serde(default, skip_serializing_if = "is_false")]
+    non_empty: bool,
+    /// the file must be valid utf-8
+    #[serde(default, skip_serializing_if = "is_false")]
+    utf8: bool,
+    /// the file must be valid json (needs the `json` feature)
+    #[serde(default, skip_serializing_if = "is_false")]
+    json: bool,
+    /// the exact permissions of the file in octal, like `"0600"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    /// the file must be executable
+    #[serde(default, skip_serializing_if = "is_false")]
+    executable: bool,
+    /// the file must be readonly
+    #[serde(default, skip_serializing_if = "is_false")]
+    readonly: bool,
+  },
+  /// a directory, it contains only files matching `pattern` if it's set,
+  /// else it has the shape described by `rest` and `entries`
+  Dir {
+    /// the name of the directory
+    name: String,
+    /// the key of the entry, defaults to the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+    /// the directory may be missing
+    #[serde(default, skip_serializing_if = "is_false")]
+    optional: bool,
+    /// the exact permissions of the directory in octal, like `"0600"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    /// the directory must be executable
+    #[serde(default, skip_serializing_if = "is_false")]
+    executable: bool,
+    /// the directory must be readonly
+    #[serde(default, skip_serializing_if = "is_false")]
+    readonly: bool,
+    /// the pattern every file in the directory must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+    /// see `ShapeDef::rest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rest: Option<ShapeRest<'static>>,
+    /// the entries of the directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entries: Vec<ShapeEntryDef>,
+  },
+  /// every directory which name matches `pattern`, each of them
+  /// has the shape described by `rest` and `entries`
+  Dirs {
+    /// the pattern of the names of the directories
+    pattern: String,
+    /// the key of the entry, defaults to the pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+    /// see `ShapeDef::rest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rest: Option<ShapeRest<'static>>,
+    /// the entries of every directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    entries: Vec<ShapeEntryDef>,
+  },
+}
+
+fn is_false(value: &bool) -> bool {
+  !*value
+}
+
+fn invalid_schema(message: String) -> Error {
+  Error::new2(ErrorKind::InvalidSchema, message)
+}
+
+fn check_pattern(pattern: &str) -> error::Result<()> {
+  match Pattern::new(pattern) {
+    Ok(_) => Ok(()),
+    Err(e) => Err(invalid_schema(e.message)),
+  }
+}
+
+fn check_rest(rest: &Option<ShapeRest<'static>>) -> error::Result<()> {
+  if let Some(ShapeRest::Pattern(pattern)) = rest {
+    check_pattern(pattern)?;
+  }
+  Ok(())
+}
+
+fn to_schema(
+  rest: Option<ShapeRest<'static>>,
+  entries: Vec<ShapeEntryDef>,
+) -> error::Result<ShapeSchema> {
+  check_rest(&rest)?;
+  let mut schema = vec![];
+  for entry in entries {
+    schema.push(entry.into_item()?);
+  }
+  if let Some(rest) = rest {
+    schema.push(ShapeItem::Rest(rest));
+  }
+  Ok(schema)
+}
+
+fn to_defs(
+  schema: &[ShapeItem],
+) -> error::Result<(Option<ShapeRest<'static>>, Vec<ShapeEntryDef>)> {
+  let mut rest = None;
+  let mut entries = vec![];
+  for item in schema {
+    match item {
+      ShapeItem::Rest(item_rest) => rest = Some(item_rest.clone()),
+      item => entries.push(ShapeEntryDef::from_item(item)?),
+    }
+  }
+  Ok((rest, entries))
+}
+
+fn identifier_or(identifier: Option<String>, default: &str) -> String {
+  identifier.unwrap_or_else(|| default.to_string())
+}
+
+fn identifier_if_differs(identifier: &str, default: &str) -> Option<String> {
+  if identifier == default {
+    None
+  } else {
+    Some(identifier.to_string())
+  }
+}
+
+fn options(optional: bool, mode: ModeRequirement) -> ShapeItemOptions<'static> {
+  ShapeItemOptions {
+    optional,
+    mode,
+    ..ShapeItemOptions::DEFAULT
+  }
+}
+
+fn mode_requirement(
+  mode: Option<String>,
+  executable: bool,
+  readonly: bool,
+) -> error::Result<ModeRequirement> {
+  let mode = match mode {
+    Some(mode) => {
+      let digits = mode.trim_start_matches("0o");
+      match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Some(mode),
+        _ => return Err(invalid_schema(format!("invalid mode \"{}\"", mode))),
+      }
+    }
+    None => None,
+  };
+  Ok(ModeRequirement {
+    mode,
+    executable,
+    readonly,
+  })
+}
+
+fn mode_fields(requirement: &ModeRequirement) -> (Option<String>, bool, bool) {
+  let mode = requirement.mode.map(|mode| format!("{:04o}", mode));
+  (mode, requirement.executable, requirement.readonly)
+}
+
+impl ShapeEntryDef {
+  /// converts the entry to an entry of an owned schema
+  pub fn into_item(self) -> error::Result<ShapeItem> {
+    match self {
+      ShapeEntryDef::File {
+        name,
+        identifier,
+        optional,
+        content,
+        max_size,
+        min_size,
+        non_empty,
+        utf8,
+        json,
+        mode,
+        executable,
+        readonly,
+      } => {
+        let mode = mode_requirement(mode, executable, readonly)?;
+        let mut validators = vec![];
+        validators.extend(max_size.map(ContentValidator::MaxSize));
+        validators.extend(min_size.map(ContentValidator::MinSize));
+        if non_empty {
+          validators.push(ContentValidator::NonEmpty);
+        }
+        if utf8 {
+          validators.push(ContentValidator::Utf8);
+        }
+        if json {
+          #[cfg(feature = "json")]
+          validators.push(ContentValidator::Json);
+          #[cfg(not(feature = "json"))]
+          return Err(invalid_schema(format!(
+            "the json check of file \"{}\" needs the json feature",
+            name
+          )));
+        }
+        Ok(ShapeItem::File(
+          identifier_or(identifier, &name),
+          name,
+          ShapeItemOptions {
+            optional,
+            content: content.map(|content| Cow::Owned(content.into_bytes())),
+            validators: Cow::Owned(validators),
+            mode,
+          },
+        ))
+      }
+      ShapeEntryDef::Dir {
+        name,
+        identifier,
+        optional,
+        mode,
+        executable,
+        readonly,
+        pattern: Some(pattern),
+        rest,
+        entries,
+      } => {
+        let mode = mode_requirement(mode, executable, readonly)?;
+        if rest.is_some() || !entries.is_empty() {
+          return Err(invalid_schema(format!(
+            "dir \"{}\" can't have both a pattern and entries or rest",
+            name
+          )));
+        }
+        check_pattern(&pattern)?;
+        Ok(ShapeItem::DirectoryPattern(
+          identifier_or(identifier, &name),
+          name,
+          pattern,
+          options(optional, mode),
+        ))
+      }
+      ShapeEntryDef::Dir {
+        name,
+        identifier,
+        optional,
+        mode,
+        executable,
+        readonly,
+        pattern: None,
+        rest,
+        entries,
+      } => Ok(ShapeItem::DirectorySchema(
+        identifier_or(identifier, &name),
+        name,
+        to_schema(rest, entries)?,
+        options(optional, mode_requirement(mode, executable, readonly)?),
+      )),
+      ShapeEntryDef::Dirs {
+        pattern,
+        identifier,
+        rest,
+        entries,
+      } => {
+        check_pattern(&pattern)?;
+        Ok(ShapeItem::DirectorySchemaPattern(
+          identifier_or(identifier, &pattern),
+          pattern,
+          to_schema(rest, entries)?,
+          ShapeItemOptions::DEFAULT,
+        ))
+      }
+    }
+  }
+  /// converts an entry of an owned schema to an entry definition
+  ///
+  /// fails if the content of a file is not valid utf-8, a file has a `validate_with`
+  /// check or the item is a rest
+  pub fn from_item(item: &ShapeItem) -> error::Result<Self> {
+    match item {
+      ShapeItem::File(identifier, name, options) => {
+        let content = match &options.content {
+          Some(content) => match String::from_utf8(content.to_vec()) {
+            Ok(content) => Some(content),
+            Err(_) => {
+              return Err(invalid_schema(format!(
+                "the content of file \"{}\" is not valid utf-8",
+                name
+              )))
+            }
+          },
+          None => None,
+        };
+        let (mut max_size, mut min_size, mut non_empty, mut utf8) = (None, None, false, false);
+        #[cfg(feature = "json")]
+        let json = options.validators.contains(&ContentValidator::Json);
+        #[cfg(not(feature = "json"))]
+        let json = false;
+        for validator in options.validators.iter() {
+          match validator {
+            ContentValidator::MaxSize(size) => max_size = Some(*size),
+            ContentValidator::MinSize(size) => min_size = Some(*size),
+            ContentValidator::NonEmpty => non_empty = true,
+            ContentValidator::Utf8 => utf8 = true,
+            #[cfg(feature = "json")]
+            ContentValidator::Json => {}
+            ContentValidator::With(_) => {
+              return Err(invalid_schema(format!(
+                "the validate_with check of file \"{}\" can't be exported",
+                name
+              )))
+            }
+          }
+        }
+        let (mode, executable, readonly) = mode_fields(&options.mode);
+        Ok(ShapeEntryDef::File {
+          name: name.clone(),
+          identifier: identifier_if_differs(identifier, name),
+          optional: options.optional,
+          content,
+          max_size,
+          min_size,
+          non_empty,
+          utf8,
+          json,
+          mode,
+          executable,
+          readonly,
+        })
+      }
+      ShapeItem::DirectoryPattern(identifier, name, pattern, options) => {
+        let (mode, executable, readonly) = mode_fields(&options.mode);
+        Ok(ShapeEntryDef::Dir {
+          name: name.clone(),
+          identifier: identifier_if_differs(identifier, name),
+          optional: options.optional,
+          mode,
+          executable,
+          readonly,
+          pattern: Some(pattern.clone()),
+          rest: None,
+          entries: vec![],
+        })
+      }
+      ShapeItem::DirectorySchema(identifier, name, schema, options) => {
+        let (rest, entries) = to_defs(schema)?;
+        let (mode, executable, readonly) = mode_fields(&options.mode);
+        Ok(ShapeEntryDef::Dir {
+          name: name.clone(),
+          identifier: identifier_if_differs(identifier, name),
+          optional: options.optional,
+          mode,
+          executable,
+          readonly,
+          pattern: None,
+          rest,
+          entries,
+        })
+      }
+      ShapeItem::DirectorySchemaPattern(identifier, pattern, schema, _) => {
+        let (rest, entries) = to_defs(schema)?;
+        Ok(ShapeEntryDef::Dirs {
+          pattern: pattern.clone(),
+          identifier: identifier_if_differs(identifier, pattern),
+          rest,
+          entries,
+        })
+      }
+      ShapeItem::Rest(_) => Err(invalid_schema(
+        "rest can't be converted to an entry".to_string(),
+      )),
+      ShapeItem::Variants(_) => Err(invalid_schema(
+        "the variants of an enum shape can't be written to a schema file".to_string(),
+      )),
+    }
+  }
+}
+
+impl ShapeDef {
+  /// converts an owned schema to a definition
+  ///
+  /// fails if the content of a file is not valid utf-8 or the schema has variants
+  pub fn from_schema(schema: &[ShapeItem]) -> error::Result<Self> {
+    let (rest, entries) = to_defs(schema)?;
+    Ok(ShapeDef { rest, entries })
+  }
+  /// exports the schema of a `#[derive(Shape)]` struct
+  /// ```
+  /// # #[cfg(feature = "yaml")]
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::ShapeDef;
+  /// use fs_pro::{File, Shape};
+  ///
+  /// #[derive(Shape)]
+  /// struct MyShapedDir {
+  ///   #[name = "my_file.txt"]
+  ///   pub my_file: File,
+  /// }
+  ///
+  /// let yaml = ShapeDef::describe::<MyShapedDir>()?.to_yaml()?;
+  /// # Ok(())
+  /// # }
+  /// # #[cfg(not(feature = "yaml"))]
+  /// # fn main() {}
+  /// ```
+  pub fn describe<T: ShapeDescribe>() -> error::Result<Self> {
+    ShapeDef::from_schema(&super::schema_from_static(T::shape_describe()))
+  }
+  /// converts the definition to an owned schema, checking every pattern
+  pub fn into_schema(self) -> error::Result<ShapeSchema> {
+    to_schema(self.rest, self.entries)
+  }
+  /// converts the definition to a shape
+  pub fn build(self) -> error::Result<DynShape> {
+    Ok(DynShape::new(self.into_schema()?))
+  }
+  /// reads a definition from a file, the format is chosen by the extension
+  /// (`.json`, `.yaml`, `.yml` or `.toml`) depending on the enabled features,
+  /// it's only there with at least one of them
+  /// ```no_run
+  /// # fn main() -> fs_pro::error::Result<()> {
+  /// use fs_pro::shape::ShapeDef;
+  ///
+  /// let shape = ShapeDef::load("layout.yaml")?.build()?;
+  /// if let Err(errors) = shape.validate("target") {
+  ///   println!("target doesn't match the shape: {:?}", errors);
+  /// }
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+  pub fn load<P: AsRef<Path>>(path: P) -> error::Result<Self> {
+    let path = path.as_ref();
+    let text = error::result_from_io(std::fs::read_to_string(path))?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let res = match extension {
+      #[cfg(feature = "json")]
+      "json" => ShapeDef::from_json(&text),
+      #[cfg(feature = "yaml")]
+      "yaml" | "yml" => ShapeDef::from_yaml(&text),
+      #[cfg(feature = "toml")]
+      "toml" => ShapeDef::from_toml(&text),
+      _ => Err(invalid_schema(format!(
+        "unsupported schema format \"{}\"",
+        extension
+      ))),
+    };
+    res.map_err(|e| e.set_path(path.to_path_buf()))
+  }
+  /// parses a definition from json
+  #[cfg(feature = "json")]
+  pub fn from_json(text: &str) -> error::Result<Self> {
+    serde_json::from_str(text).map_err(json_error)
+  }
+  /// writes the definition as pretty json
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> error::Result<String> {
+    serde_json::to_string_pretty(self).map_err(json_error)
+  }
+  /// parses a definition from yaml
+  #[cfg(feature = "yaml")]
+  pub fn from_yaml(text: &str) -> error::Result<Self> {
+    serde_yaml::from_str(text).map_err(yaml_error)
+  }
+  /// writes the definition as yaml
+  #[cfg(feature = "yaml")]
+  pub fn to_yaml(&self) -> error::Result<String> {
+    serde_yaml::to_string(self).map_err(yaml_error)
+  }
+  /// parses a definition from toml
+  #[cfg(feature = "toml")]
+  pub fn from_toml(text: &str) -> error::Result<Self> {
+    toml::from_str(text).map_err(toml_error)
+  }
+  /// writes the definition as toml
+  #[cfg(feature = "toml")]
+  pub fn to_toml(&self) -> error::Result<String> {
+    toml::to_string(self).map_err(toml_error)
+  }
+}
+
+#[cfg(feature = "json")]
+fn json_error(e: serde_json::Error) -> Error {
+  let message = e.to_string();
+  Error::new2(ErrorKind::JsonError(e), message)
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_error(e: serde_yaml::Error) -> Error {
+  let message = e.to_string();
+  Error::new2(ErrorKind::YamlError(e), message)
+}
+
+#[cfg(feature = "toml")]
+fn toml_error<E: std::error::Error + Send + Sync + 'static>(e: E) -> Error {
+  let message = e.to_string();
+  Error::new2(ErrorKind::TomlError(Box::new(e)), message)
+}
@@ -0,0 +1,320 @@
+use super::{
+  params, ContentValidator, EntryType, ShapeItem, ShapeItemOptions, ShapeRest, ShapeSchema,
+};
+use crate::dir::Dir;
+use crate::error::{self, Error, ErrorKind};
+use std::collections::HashSet;
+use std::path::Path;
+
+const KEYWORDS: &[&str] = &[
+  "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+  "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+  "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+  "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try",
+  "typeof", "unsized", "virtual", "yield",
+];
+
+// converts a file name to a valid field identifier, like `My File.txt` to `my_file_txt`
+fn field_identifier(name: &str) -> String {
+  let mut res = String::new();
+  for c in name.chars() {
+    if c.is_ascii_alphanumeric() {
+      res.push(c.to_ascii_lowercase());
+    } else if !res.ends_with('_') {
+      res.push('_');
+    }
+  }
+  let mut res = res.trim_matches('_').to_string();
+  if res.is_empty() {
+    res.push_str("entry");
+  }
+  if res.starts_with(|c: char| c.is_ascii_digit()) {
+    res.insert(0, '_');
+  }
+  if KEYWORDS.contains(&res.as_str()) {
+    res.push('_');
+  }
+  res
+}
+
+fn is_identifier(name: &str) -> bool {
+  !name.is_empty()
+    && !name.starts_with(|c: char| c.is_ascii_digit())
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    && !KEYWORDS.contains(&name)
+    && name != "_"
+}
+
+// converts an identifier to a struct name, like `hi_dir` to `HiDir`
+fn struct_name(identifier: &str) -> String {
+  let mut res: String = identifier
+    .split('_')
+    .filter(|part| !part.is_empty())
+    .map(|part| {
+      let mut chars = part.chars();
+      match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+      }
+    })
+    .collect();
+  if res.is_empty() || res.starts_with(|c: char| c.is_ascii_digit()) {
+    res.insert_str(0, "Dir");
+  }
+  if res == "Self" {
+    res.push('_');
+  }
+  res
+}
+
+// returns name, or name with a number added if it's already taken
+fn unique(name: String, taken: &mut HashSet<String>) -> String {
+  let mut res = name.clone();
+  let mut i = 2;
+  while taken.contains(&res) {
+    res = format!("{}_{}", name, i);
+    i += 1;
+  }
+  taken.insert(res.clone());
+  res
+}
+
+// the pattern of a folder that has only files with the same extension, like `*.log`
+fn homogeneous_pattern(entries: &[(String, EntryType)]) -> Option<String> {
+  if entries.is_empty() {
+    return Some("*".to_string());
+  }
+  if entries.len() < 2 {
+    return None;
+  }
+  let mut extension = None;
+  for (name, entry_type) in entries {
+    if *entry_type != EntryType::File {
+      return None;
+    }
+    let ext = Path::new(name).extension()?.to_str()?;
+    match extension {
+      None => extension = Some(ext),
+      Some(extension) if extension != ext => return None,
+      _ => {}
+    }
+  }
+  let extension = extension?;
+  if extension.contains(|c| "*?[]{}!\\".contains(c)) {
+    return None;
+  }
+  Some(format!("*.{}", extension))
+}
+
+fn read_sorted(dir: &Dir) -> error::Result<Vec<(String, EntryType)>> {
+  let mut entries = vec![];
+  for entry in dir.read()? {
+    let name = entry.file_name().to_string_lossy().to_string();
+    let entry_type = EntryType::of(entry.path()).unwrap_or(EntryType::File);
+    entries.push((name, entry_type));
+  }
+  entries.sort_by(|a, b| a.0.cmp(&b.0));
+  Ok(entries)
+}
+
+// infers the schema of a directory, files become files, folders that are empty or
+// have only files with the same extension become pattern dirs and any other folder
+// becomes a nested schema
+pub(super) fn infer(dir: &Dir) -> error::Result<ShapeSchema> {
+  let mut schema = vec![];
+  let mut taken = HashSet::new();
+  for (name, entry_type) in read_sorted(dir)? {
+    let identifier = unique(field_identifier(&name), &mut taken);
+    let options = ShapeItemOptions::DEFAULT;
+    let item = match entry_type {
+      EntryType::File => ShapeItem::File(identifier, name, options),
+      EntryType::Dir => {
+        let sub_dir = Dir::new(dir.path.join(&name))?;
+        match homogeneous_pattern(&read_sorted(&sub_dir)?) {
+          Some(pattern) => ShapeItem::DirectoryPattern(identifier, name, pattern, options),
+          None => ShapeItem::DirectorySchema(identifier, name, infer(&sub_dir)?, options),
+        }
+      }
+    };
+    schema.push(item);
+  }
+  Ok(schema)
+}
+
+// checks that a name or pattern can be written in a `#[derive(Shape)]` attribute
+// the derive macro only accepts names with valid placeholders (see `Shape::create_at_with`)
+fn attr_value(value: &str, is_name: bool) -> error::Result<String> {
+  if value.contains('/') || (is_name && !params::is_valid(value)) {
+    return Err(Error::new2(
+      ErrorKind::InvalidSchema,
+      format!(
+        "\"{}\" can't be used in a #[derive(Shape)] attribute",
+        value
+      ),
+    ));
+  }
+  Ok(format!("{:?}", value))
+}
+
+struct Generator {
+  structs: Vec<String>,
+  struct_names: HashSet<String>,
+  uses_file: bool,
+  uses_dir: bool,
+}
+
+impl Generator {
+  fn generate(&mut self, name: String, schema: &[ShapeItem]) -> error::Result<String> {
+    let name = unique(name, &mut self.struct_names);
+    let index = self.structs.len();
+    self.structs.push(String::new());
+    if let [ShapeItem::Variants(variants)] = schema {
+      let mut out = String::from("#[derive(Shape)]\n");
+      out.push_str(&format!("pub enum {} {{\n", name));
+      for (variant, schema) in variants {
+        let variant = match variant {
+          variant if is_identifier(variant) => variant.to_string(),
+          variant => struct_name(&field_identifier(variant)),
+        };
+        let ty = self.generate(format!("{}{}", name, variant), schema)?;
+        out.push_str(&format!("  {}({}),\n", variant, ty));
+      }
+      out.push_str("}\n");
+      self.structs[index] = out;
+      return Ok(name);
+    }
+    let mut out = String::from("#[derive(Shape)]\n");
+    let mut fields = String::new();
+    let mut taken = HashSet::new();
+    for item in schema {
+      let identifier = match item.identifier() {
+        identifier if is_identifier(identifier) => identifier.to_string(),
+        identifier => field_identifier(identifier),
+      };
+      let identifier = &unique(identifier, &mut taken);
+      let options = item.options();
+      if let Some(mode) = options.mode.mode {
+        fields.push_str(&format!("  #[mode = 0o{:o}]\n", mode));
+      }
+      if options.mode.executable {
+        fields.push_str("  #[executable]\n");
+      }
+      if options.mode.readonly {
+        fields.push_str("  #[readonly]\n");
+      }
+      let ty = match item {
+        ShapeItem::Rest(ShapeRest::Allow) => continue,
+        ShapeItem::Rest(ShapeRest::Forbid) => {
+          out.push_str("#[rest = \"forbid\"]\n");
+          continue;
+        }
+        ShapeItem::Rest(ShapeRest::Pattern(pattern)) => {
+          out.push_str(&format!(
+            "#[rest_pattern = {}]\n",
+            attr_value(pattern, false)?
+          ));
+          continue;
+        }
+        ShapeItem::File(_, name, _) => {
+          if name != identifier {
+            fields.push_str(&format!("  #[name = {}]\n", attr_value(name, true)?));
+          }
+          if let Some(content) = &options.content {
+            if let Ok(content) = std::str::from_utf8(content) {
+              fields.push_str(&format!("  #[content = {:?}]\n", content));
+            }
+          }
+          for validator in options.validators.iter() {
+            let attr = match validator {
+              ContentValidator::MaxSize(size) => format!("max_size = {}", size),
+              ContentValidator::MinSize(size) => format!("min_size = {}", size),
+              ContentValidator::NonEmpty => "non_empty".to_string(),
+              ContentValidator::Utf8 => "utf8".to_string(),
+              #[cfg(feature = "json")]
+              ContentValidator::Json => "json".to_string(),
+              // the path of the function isn't known at runtime
+              ContentValidator::With(_) => continue,
+            };
+            fields.push_str(&format!("  #[{}]\n", attr));
+          }
+          self.uses_file = true;
+          "File".to_string()
+        }
+        ShapeItem::DirectoryPattern(_, name, pattern, _) => {
+          if name != identifier {
+            fields.push_str(&format!("  #[name = {}]\n", attr_value(name, true)?));
+          }
+          fields.push_str(&format!("  #[pattern = {}]\n", attr_value(pattern, false)?));
+          self.uses_dir = true;
+          "Dir".to_string()
+        }
+        ShapeItem::DirectorySchema(_, name, schema, _) => {
+          if name != identifier {
+            fields.push_str(&format!("  #[name = {}]\n", attr_value(name, true)?));
+          }
+          self.generate(struct_name(identifier), schema)?
+        }
+        ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) => {
+          fields.push_str(&format!("  #[pattern = {}]\n", attr_value(pattern, false)?));
+          format!("Vec<{}>", self.generate(struct_name(identifier), schema)?)
+        }
+        // only the schema of an enum has variants
+        ShapeItem::Variants(_) => {
+          return Err(Error::new(
+            ErrorKind::InvalidSchema,
+            "variants must be the only entry of a schema",
+          ))
+        }
+      };
+      let ty = if options.optional {
+        format!("Option<{}>", ty)
+      } else {
+        ty
+      };
+      fields.push_str(&format!("  pub {}: {},\n", identifier, ty));
+    }
+    if fields.is_empty() {
+      out.push_str(&format!("pub struct {} {{}}\n", name));
+    } else {
+      out.push_str(&format!("pub struct {} {{\n{}}}\n", name, fields));
+    }
+    self.structs[index] = out;
+    Ok(name)
+  }
+}
+
+// generates the source of `#[derive(Shape)]` structs for a schema,
+// the root struct is named name and nested schemas get a struct named after their field
+pub(super) fn to_rust(schema: &[ShapeItem], name: &str) -> error::Result<String> {
+  let mut generator = Generator {
+    structs: vec![],
+    // the names the generated code imports or uses can't be taken by a struct
+    struct_names: ["Shape", "File", "Dir", "Vec", "Option"]
+      .iter()
+      .map(|name| name.to_string())
+      .collect(),
+    uses_file: false,
+    uses_dir: false,
+  };
+  let name = match name {
+    name if is_identifier(name) => name.to_string(),
+    name => struct_name(&field_identifier(name)),
+  };
+  generator.generate(name, schema)?;
+  let imports = match (generator.uses_dir, generator.uses_file) {
+    (true, true) => "Dir, File, Shape",
+    (true, false) => "Dir, Shape",
+    (false, true) => "File, Shape",
+    (false, false) => "Shape",
+  };
+  let mut out = if imports.contains(',') {
+    format!("use fs_pro::{{{}}};\n", imports)
+  } else {
+    format!("use fs_pro::{};\n", imports)
+  };
+  for st in generator.structs {
+    out.push('\n');
+    out.push_str(&st);
+  }
+  Ok(out)
+}
@@ -0,0 +1,223 @@
+use super::journal::{self, Change, Journal};
+use super::{
+  closest_variant, create_shape_inst, matching_dirs, params, schema_from_static, CreateOptions,
+  Creation, EntryType, ShapeDescribe, ShapeHookResult, ShapeInst, ShapeItem,
+};
+use crate::dir::Dir;
+use crate::error::{self, Error, ErrorKind};
+use crate::file::File;
+use crate::pattern::Pattern;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// operations on a whole instance of a shape, they only touch the entries declared
+/// by the shape (files matching the pattern of a `Dir` field, the folders of `Vec`
+/// and `HashMap` fields and so on), undeclared entries are left where they are
+///
+/// `#[derive(Shape)]` implements it for structs with a `#[root] pub root: Dir` field
+/// (the folder of the instance is kept there) and for enums marked `#[instance]`,
+/// the variants of those must all hold shapes that implement it
+/// ```no_run
+/// # use fs_pro::{File, Shape};
+/// # #[derive(Shape)]
+/// # struct ConfigDir {
+/// #   #[root]
+/// #   pub root: fs_pro::Dir,
+/// #   #[name = "app.toml"]
+/// #   pub app: File,
+/// # }
+/// # #[derive(Shape)]
+/// # struct Project {
+/// #   #[root]
+/// #   pub root: fs_pro::Dir,
+/// #   pub config_dir: ConfigDir,
+/// # }
+/// # fn main() -> fs_pro::error::Result<()> {
+/// use fs_pro::shape::ShapeInstance;
+///
+/// # let shape: Shape<Project> = Shape::new();
+/// let project = shape.create_at("project")?;
+/// println!("{}", project.config_dir.root().path.display());
+/// let backup = project.copy("backup")?;
+/// let project = project.move_to("archive")?;
+/// project.delete()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait ShapeInstance: ShapeDescribe {
+  /// the folder the instance was created or opened at, it's the `#[root]` field
+  fn root(&self) -> Dir;
+  /// copies the declared entries to dest (with `File::copy`) and returns the copy
+  ///
+  /// fails if a declared file already exists in dest, everything copied is removed then
+  fn copy<P: AsRef<Path>>(&self, dest: P) -> error::Result<Self> {
+    let schema = params::literal(&schema_from_static(Self::shape_describe()))?;
+    let inst = transfer(&self.root().path, dest.as_ref(), &schema, false)?;
+    Self::shape_new(inst)
+  }
+  /// moves the declared entries to dest (with `File::move_to`) and returns the moved
+  /// instance, the folders left empty are deleted
+  ///
+  /// fails if a declared file already exists in dest, everything moved is moved back then
+  fn move_to<P: AsRef<Path>>(self, dest: P) -> error::Result<Self> {
+    let schema = params::literal(&schema_from_static(Self::shape_describe()))?;
+    let inst = transfer(&self.root().path, dest.as_ref(), &schema, true)?;
+    Self::shape_new(inst)
+  }
+  /// deletes the declared entries, folders are only deleted if they end up empty
+  fn delete(self) -> error::Result<()> {
+    let root = self.root();
+    let schema = params::literal(&schema_from_static(Self::shape_describe()))?;
+    let entries = declared(&root.path, &schema)?;
+    for (path, entry_type) in entries.iter().rev() {
+      let path = root.path.join(path);
+      match entry_type {
+        EntryType::File => File::new(&path)?.delete()?,
+        EntryType::Dir => remove_if_empty(&path)?,
+      }
+    }
+    remove_if_empty(&root.path)
+  }
+}
+
+fn remove_if_empty(path: &Path) -> error::Result<()> {
+  let empty = error::result_from_io(fs::read_dir(path))
+    .map_err(|e| e.set_path(path.to_path_buf()))?
+    .next()
+    .is_none();
+  if empty {
+    error::result_from_io(fs::remove_dir(path)).map_err(|e| e.set_path(path.to_path_buf()))?;
+  }
+  Ok(())
+}
+
+// the existing entries of root declared by target relative to root, a folder comes
+// before it's entries
+pub(super) fn declared(
+  root: &Path,
+  target: &[ShapeItem],
+) -> error::Result<Vec<(PathBuf, EntryType)>> {
+  let mut entries = vec![];
+  declared_in(root, Path::new(""), target, &mut entries)?;
+  Ok(entries)
+}
+
+fn declared_in(
+  root: &Path,
+  relative: &Path,
+  target: &[ShapeItem],
+  entries: &mut Vec<(PathBuf, EntryType)>,
+) -> error::Result<()> {
+  let dir = Dir::new(root.join(relative))?;
+  for item in target {
+    let path = item.name().map(|name| relative.join(name));
+    let is = |entry_type| {
+      path
+        .as_ref()
+        .map(|path| EntryType::of(root.join(path)) == Some(entry_type))
+        .unwrap_or(false)
+    };
+    match item {
+      ShapeItem::File(_, _, _) if is(EntryType::File) => {
+        entries.push((path.unwrap(), EntryType::File));
+      }
+      ShapeItem::DirectoryPattern(_, name, pattern, _) if is(EntryType::Dir) => {
+        let path = path.unwrap();
+        entries.push((path.clone(), EntryType::Dir));
+        let compiled = Pattern::new(pattern)?;
+        let mut files = dir.get_dir(name)?.read()?;
+        files.sort_by_key(|entry| entry.path());
+        for entry in files {
+          if entry.is_file() && compiled.is_match(entry.file_name()) {
+            entries.push((path.join(entry.file_name()), EntryType::File));
+          }
+        }
+      }
+      ShapeItem::DirectorySchema(_, _, schema, _) if is(EntryType::Dir) => {
+        let path = path.unwrap();
+        entries.push((path.clone(), EntryType::Dir));
+        declared_in(root, &path, schema, entries)?;
+      }
+      ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) => {
+        for sub_dir in matching_dirs(&dir, target, &Pattern::new(pattern)?)? {
+          let path = relative.join(sub_dir.name()?);
+          entries.push((path.clone(), EntryType::Dir));
+          declared_in(root, &path, schema, entries)?;
+        }
+      }
+      ShapeItem::Variants(variants) => {
+        let (i, _) = closest_variant(&dir, variants, "");
+        if let Some((_, schema)) = variants.get(i) {
+          declared_in(root, relative, schema, entries)?;
+        }
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+// copies (or moves) the declared entries of from to to and binds the result,
+// it's rolled back if anything fails
+fn transfer(
+  from: &Path,
+  to: &Path,
+  target: &[ShapeItem],
+  move_entries: bool,
+) -> error::Result<ShapeInst> {
+  let mut journal = Journal::new();
+  let mut run = || -> error::Result<ShapeInst> {
+    let entries = declared(from, target)?;
+    // the missing parents are created one by one so the rollback removes them all
+    let missing: Vec<&Path> = to
+      .ancestors()
+      .take_while(|path| !path.as_os_str().is_empty() && !path.exists())
+      .collect();
+    for path in missing.into_iter().rev() {
+      Dir::new(path)?.create()?;
+      journal.push(Change::Created(path.to_path_buf()));
+    }
+    for (path, entry_type) in &entries {
+      let dest = to.join(path);
+      match entry_type {
+        EntryType::Dir if dest.is_dir() => {}
+        EntryType::Dir => {
+          Dir::new(&dest)?.create()?;
+          journal.push(Change::Created(dest));
+        }
+        EntryType::File if dest.exists() => {
+          return Err(Error::new_from_kind(ErrorKind::AlreadyExists).set_path(dest));
+        }
+        EntryType::File if move_entries => {
+          File::new(from.join(path))?.move_to(&dest)?;
+          journal.push(Change::Moved(from.join(path), dest));
+        }
+        EntryType::File => {
+          File::new(from.join(path))?.copy(&dest)?;
+          journal.push(Change::Created(dest));
+        }
+      }
+    }
+    let mut creation = Creation {
+      create: false,
+      options: &CreateOptions::new(),
+      hook: &mut |_| ShapeHookResult::Continue,
+      journal: Journal::new(),
+    };
+    let inst = create_shape_inst(to.to_path_buf(), target, &mut creation)?;
+    if move_entries {
+      // the moved folders are only deleted once nothing can fail anymore
+      for (path, entry_type) in entries.iter().rev() {
+        if let EntryType::Dir = entry_type {
+          let _ = remove_if_empty(&from.join(path));
+        }
+      }
+      let _ = remove_if_empty(from);
+    }
+    Ok(inst)
+  };
+  match run() {
+    Ok(inst) => Ok(inst),
+    Err(e) => Err(journal::fail_with_rollback(e, journal)),
+  }
+}
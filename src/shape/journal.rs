@@ -0,0 +1,63 @@
+use crate::error::{self, Error};
+use std::fs;
+use std::path::PathBuf;
+
+// a change made by create_at or migrate, it's undone if the call fails
+pub(super) enum Change {
+  // a file or folder that didn't exist before
+  Created(PathBuf),
+  // a file that existed before with it's previous content
+  Overwritten(PathBuf, Vec<u8>),
+  // a file or folder that was moved (or renamed) from the first path to the second
+  Moved(PathBuf, PathBuf),
+  // a file or folder which permissions were changed, with it's previous mode
+  #[cfg(unix)]
+  ModeChanged(PathBuf, u32),
+}
+
+// every change made by a create_at or migrate call, in order
+pub(super) type Journal = Vec<Change>;
+
+// undoes the changes in reverse order and returns the errors of the changes
+// that couldn't be undone
+fn rollback(journal: Journal) -> Vec<Error> {
+  let mut errors = vec![];
+  for change in journal.into_iter().rev() {
+    let (res, path) = match change {
+      Change::Created(path) if path.is_dir() => (fs::remove_dir(&path), path),
+      Change::Created(path) => (fs::remove_file(&path), path),
+      Change::Overwritten(path, content) => (fs::write(&path, content), path),
+      Change::Moved(from, to) => (fs::rename(&to, &from), from),
+      #[cfg(unix)]
+      Change::ModeChanged(path, mode) => {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = fs::Permissions::from_mode(mode);
+        (fs::set_permissions(&path, permissions), path)
+      }
+    };
+    if let Err(e) = error::result_from_io(res) {
+      errors.push(e.set_path(path));
+    }
+  }
+  errors
+}
+
+// rolls the journal back and adds the problems of the rollback to the message of error
+pub(super) fn fail_with_rollback(error: Error, journal: Journal) -> Error {
+  let rollback_errors = rollback(journal);
+  if rollback_errors.is_empty() {
+    return error;
+  }
+  let mut message = format!("{}\nrollback failed:", error.message);
+  for e in &rollback_errors {
+    match &e.path {
+      Some(path) => message.push_str(&format!("\n  {}: {}", path.display(), e.message)),
+      None => message.push_str(&format!("\n  {}", e.message)),
+    }
+  }
+  Error {
+    kind: error.kind,
+    message,
+    path: error.path,
+  }
+}
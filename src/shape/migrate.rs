@@ -0,0 +1,281 @@
+use super::journal::{self, Change, Journal};
+use super::{
+  create_shape_inst, validate_report, ChangeKind, CreateOptions, Creation, ShapeDescribe,
+  ShapeDiff, ShapeHookResult, ShapeInst, ShapeItem,
+};
+use crate::error::{self, Error, ErrorKind};
+use crate::pattern::Pattern;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// a step of a `Migration`, paths are relative to the migrated directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStep {
+  /// renames (or moves) a file or folder from the first path to the second
+  Rename(PathBuf, PathBuf),
+  /// moves a file or folder into a folder, keeping it's name
+  MoveInto(PathBuf, PathBuf),
+  /// moves the entries of a folder whose name matches a pattern to the folder paired
+  /// with it (the first matching pattern wins), the folder is deleted if it ends up empty
+  Split(PathBuf, Vec<(String, PathBuf)>),
+  /// deletes a file or folder
+  Delete(PathBuf),
+}
+
+/// the steps that migrate a directory from one shape to another
+/// ```
+/// use fs_pro::shape::Migration;
+///
+/// let migration = Migration::new()
+///   .rename("config.toml", "settings.toml")
+///   .move_into("notes.txt", "docs")
+///   .split("data", &[("*.log", "logs"), ("*.csv", "tables")])
+///   .delete("cache");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Migration {
+  /// the steps, run in order
+  pub steps: Vec<MigrationStep>,
+}
+
+impl Migration {
+  /// creates an empty migration
+  pub fn new() -> Self {
+    Migration { steps: vec![] }
+  }
+  /// adds a `MigrationStep::Rename`
+  pub fn rename<F: AsRef<Path>, T: AsRef<Path>>(mut self, from: F, to: T) -> Self {
+    let step = MigrationStep::Rename(from.as_ref().to_path_buf(), to.as_ref().to_path_buf());
+    self.steps.push(step);
+    self
+  }
+  /// adds a `MigrationStep::MoveInto`
+  pub fn move_into<P: AsRef<Path>, D: AsRef<Path>>(mut self, path: P, dir: D) -> Self {
+    let step = MigrationStep::MoveInto(path.as_ref().to_path_buf(), dir.as_ref().to_path_buf());
+    self.steps.push(step);
+    self
+  }
+  /// adds a `MigrationStep::Split`
+  pub fn split<P: AsRef<Path>>(mut self, path: P, parts: &[(&str, &str)]) -> Self {
+    let parts = parts
+      .iter()
+      .map(|(pattern, to)| (pattern.to_string(), PathBuf::from(to)))
+      .collect();
+    self
+      .steps
+      .push(MigrationStep::Split(path.as_ref().to_path_buf(), parts));
+    self
+  }
+  /// adds a `MigrationStep::Delete`
+  pub fn delete<P: AsRef<Path>>(mut self, path: P) -> Self {
+    self
+      .steps
+      .push(MigrationStep::Delete(path.as_ref().to_path_buf()));
+    self
+  }
+  /// a migration that renames the entries renamed in diff, renames of entries in
+  /// `Vec` and `HashMap` fields are skipped
+  pub fn from_diff(diff: &ShapeDiff) -> Self {
+    let mut migration = Migration::new();
+    for change in &diff.changes {
+      if let ChangeKind::Renamed { from } = &change.kind {
+        let in_pattern = |path: &Path| {
+          path
+            .to_str()
+            .map(|path| path.contains(|c| "*?[]{}!".contains(c)))
+            .unwrap_or(false)
+        };
+        if !in_pattern(from) && !in_pattern(&change.path) {
+          migration = migration.rename(from, &change.path);
+        }
+      }
+    }
+    migration
+  }
+}
+
+/// declares how a directory is migrated from the shape `Self` to the shape `New`,
+/// see `Shape::migrate`
+/// ```
+/// # use fs_pro::{Dir, File, Shape};
+/// # #[derive(Shape)]
+/// # struct LayoutV1 {
+/// #   #[name = "notes.txt"]
+/// #   pub notes: File,
+/// #   #[pattern = "*"]
+/// #   pub cache: Dir,
+/// # }
+/// # #[derive(Shape)]
+/// # struct LayoutV2 {
+/// #   #[pattern = "*"]
+/// #   pub docs: Dir,
+/// # }
+/// use fs_pro::shape::{Migrate, Migration};
+///
+/// impl Migrate<LayoutV2> for LayoutV1 {
+///   fn migration() -> Migration {
+///     Migration::new().delete("cache").move_into("notes.txt", "docs")
+///   }
+/// }
+/// ```
+pub trait Migrate<New: ShapeDescribe>: ShapeDescribe + Sized {
+  /// the steps of the migration, by default the renames found by `ShapeDiff::between_types`
+  /// (none if one of the shapes has a placeholder in a name)
+  fn migration() -> Migration {
+    ShapeDiff::between_types::<Self, New>()
+      .map(|diff| Migration::from_diff(&diff))
+      .unwrap_or_default()
+  }
+}
+
+struct Migrator<'a> {
+  root: &'a Path,
+  journal: Journal,
+  // where deleted entries are kept until the migration succeeds
+  backup: PathBuf,
+  backups: usize,
+}
+
+fn not_found(path: PathBuf) -> Error {
+  Error::new_from_kind(ErrorKind::NotFound).set_path(path)
+}
+
+impl Migrator<'_> {
+  // creates the missing folders of path
+  fn create_dirs(&mut self, path: &Path) -> error::Result<()> {
+    if path.as_os_str().is_empty() || path.exists() {
+      return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+      self.create_dirs(parent)?;
+    }
+    error::result_from_io(fs::create_dir(path)).map_err(|e| e.set_path(path.to_path_buf()))?;
+    self.journal.push(Change::Created(path.to_path_buf()));
+    Ok(())
+  }
+  fn rename(&mut self, from: PathBuf, to: PathBuf) -> error::Result<()> {
+    if !from.exists() {
+      return Err(not_found(from));
+    }
+    if to.exists() {
+      return Err(Error::new_from_kind(ErrorKind::AlreadyExists).set_path(to));
+    }
+    if let Some(parent) = to.parent() {
+      self.create_dirs(parent)?;
+    }
+    error::result_from_io(fs::rename(&from, &to)).map_err(|e| e.set_path(from.clone()))?;
+    self.journal.push(Change::Moved(from, to));
+    Ok(())
+  }
+  fn delete(&mut self, path: PathBuf) -> error::Result<()> {
+    if !path.exists() {
+      return Err(not_found(path));
+    }
+    if self.backups == 0 {
+      let backup = self.backup.clone();
+      self.create_dirs(&backup)?;
+    }
+    self.backups += 1;
+    let to = self.backup.join(self.backups.to_string());
+    self.rename(path, to)
+  }
+  fn run(&mut self, step: &MigrationStep) -> error::Result<()> {
+    let root = self.root;
+    match step {
+      MigrationStep::Rename(from, to) => self.rename(root.join(from), root.join(to)),
+      MigrationStep::MoveInto(path, dir) => {
+        let name = path
+          .file_name()
+          .ok_or_else(|| Error::new_from_kind(ErrorKind::PathNoFilenameFound))?;
+        self.rename(root.join(path), root.join(dir).join(name))
+      }
+      MigrationStep::Split(path, parts) => {
+        let from = root.join(path);
+        let mut compiled = vec![];
+        for (pattern, to) in parts {
+          compiled.push((Pattern::new(pattern)?, root.join(to)));
+        }
+        let read =
+          error::result_from_io(fs::read_dir(&from)).map_err(|e| e.set_path(from.clone()))?;
+        let mut names = vec![];
+        for entry in read {
+          names.push(error::result_from_io(entry)?.file_name());
+        }
+        names.sort();
+        for name in names {
+          if let Some((_, to)) = compiled.iter().find(|(pattern, _)| pattern.is_match(&name)) {
+            self.rename(from.join(&name), to.join(&name))?;
+          }
+        }
+        let empty = error::result_from_io(fs::read_dir(&from))?.next().is_none();
+        if empty {
+          self.delete(from)?;
+        }
+        Ok(())
+      }
+      MigrationStep::Delete(path) => self.delete(root.join(path)),
+    }
+  }
+}
+
+// runs the steps, creates the missing entries of target and validates the result,
+// everything is rolled back if any of them fails
+pub(super) fn migrate<P: AsRef<Path>>(
+  path: P,
+  migration: &Migration,
+  target: &[ShapeItem],
+) -> error::Result<ShapeInst> {
+  let root = path.as_ref();
+  let name = root
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let backup_name = format!(".{}.migration-backup-{}", name, std::process::id());
+  let mut migrator = Migrator {
+    root,
+    journal: Journal::new(),
+    backup: root.parent().unwrap_or(root).join(backup_name),
+    backups: 0,
+  };
+  let mut run = || -> error::Result<ShapeInst> {
+    if !root.is_dir() {
+      return Err(Error::new_from_kind(ErrorKind::InvalidFolder).set_path(root.to_path_buf()));
+    }
+    for step in &migration.steps {
+      migrator.run(step)?;
+    }
+    let mut creation = Creation {
+      create: true,
+      options: &CreateOptions::new(),
+      hook: &mut |_| ShapeHookResult::Continue,
+      journal: Journal::new(),
+    };
+    let res = create_shape_inst(root.to_path_buf(), target, &mut creation);
+    migrator.journal.append(&mut creation.journal);
+    let res = res?;
+    let report = validate_report(root, target);
+    if !report.is_valid() {
+      return Err(
+        Error::new2(
+          ErrorKind::InvalidFolder,
+          format!(
+            "the migrated folder doesn't match the new shape\n{}",
+            report.render_tree()
+          ),
+        )
+        .set_path(root.to_path_buf()),
+      );
+    }
+    Ok(res)
+  };
+  match run() {
+    Ok(res) => {
+      if migrator.backups > 0 {
+        // the migration is done, failing to clean up the backup doesn't undo it
+        let _ = fs::remove_dir_all(&migrator.backup);
+      }
+      Ok(res)
+    }
+    Err(e) => Err(journal::fail_with_rollback(e, migrator.journal)),
+  }
+}
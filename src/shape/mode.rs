@@ -0,0 +1,90 @@
+#[cfg(unix)]
+use crate::error;
+use std::fmt;
+#[cfg(unix)]
+use std::path::Path;
+
+/// the permissions an entry must have, set with `#[mode = 0o600]`, `#[executable]`
+/// and `#[readonly]`, they're applied by `Shape::create_at` and checked by
+/// `Shape::validate` (only on unix)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModeRequirement {
+  /// the exact permission bits of the entry, like `0o600`
+  pub mode: Option<u32>,
+  /// every class that can read the entry can execute it too (at least the owner)
+  pub executable: bool,
+  /// nobody can write to the entry
+  pub readonly: bool,
+}
+
+impl ModeRequirement {
+  /// no requirement
+  pub const NONE: ModeRequirement = ModeRequirement {
+    mode: None,
+    executable: false,
+    readonly: false,
+  };
+  /// returns true if there is no requirement
+  pub fn is_empty(&self) -> bool {
+    *self == ModeRequirement::NONE
+  }
+  /// returns true if the permission bits of mode meet the requirement
+  pub fn is_met(&self, mode: u32) -> bool {
+    let mode = mode & 0o7777;
+    if let Some(expected) = self.mode {
+      if mode != expected {
+        return false;
+      }
+    }
+    let readable_classes = (mode & 0o444) >> 2;
+    if self.executable && (mode & 0o100 == 0 || mode & readable_classes != readable_classes) {
+      return false;
+    }
+    !(self.readonly && mode & 0o222 != 0)
+  }
+  /// the permission bits mode is changed to, to meet the requirement
+  pub fn apply(&self, mode: u32) -> u32 {
+    let mut mode = self.mode.unwrap_or(mode & 0o7777);
+    if self.executable {
+      mode |= 0o100 | ((mode & 0o444) >> 2);
+    }
+    if self.readonly {
+      mode &= !0o222;
+    }
+    mode
+  }
+}
+
+impl fmt::Display for ModeRequirement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut parts = vec![];
+    if let Some(mode) = self.mode {
+      parts.push(format!("{:04o}", mode));
+    }
+    if self.executable {
+      parts.push("executable".to_string());
+    }
+    if self.readonly {
+      parts.push("readonly".to_string());
+    }
+    write!(f, "{}", parts.join(", "))
+  }
+}
+
+// the permission bits of the entry at path
+#[cfg(unix)]
+pub(super) fn mode_of(path: &Path) -> error::Result<u32> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let metadata = error::result_from_io(std::fs::metadata(path))?;
+  Ok(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(unix)]
+pub(super) fn set_mode(path: &Path, mode: u32) -> error::Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let permissions = std::fs::Permissions::from_mode(mode);
+  error::result_from_io(std::fs::set_permissions(path, permissions))
+    .map_err(|e| e.set_path(path.to_path_buf()))
+}
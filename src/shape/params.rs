@@ -0,0 +1,157 @@
+use super::{ShapeItem, ShapeSchema};
+use crate::error::{self, Error, ErrorKind};
+use std::collections::HashMap;
+
+/// the values of the placeholders in the names of a shape, see `Shape::create_at_with`
+pub type ShapeContext = HashMap<String, String>;
+
+fn invalid_name(name: &str, message: &str) -> Error {
+  Error::new2(
+    ErrorKind::InvalidSchema,
+    format!("invalid name \"{}\": {}", name, message),
+  )
+}
+
+// splits a name into literal text and placeholder keys, `{{` and `}}` are literal braces
+fn parse(name: &str) -> error::Result<Vec<(bool, String)>> {
+  let mut parts = vec![];
+  let mut text = String::new();
+  let mut chars = name.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '{' if chars.peek() == Some(&'{') => {
+        chars.next();
+        text.push('{');
+      }
+      '}' if chars.peek() == Some(&'}') => {
+        chars.next();
+        text.push('}');
+      }
+      '{' => {
+        let mut key = String::new();
+        loop {
+          match chars.next() {
+            Some('}') => break,
+            Some('{') | None => return Err(invalid_name(name, "unclosed placeholder")),
+            Some(c) => key.push(c),
+          }
+        }
+        if key.is_empty() {
+          return Err(invalid_name(name, "empty placeholder"));
+        }
+        if !text.is_empty() {
+          parts.push((false, std::mem::take(&mut text)));
+        }
+        parts.push((true, key));
+      }
+      '}' => return Err(invalid_name(name, "unmatched '}'")),
+      c => text.push(c),
+    }
+  }
+  if !text.is_empty() {
+    parts.push((false, text));
+  }
+  Ok(parts)
+}
+
+// returns true if the placeholders of name are well formed
+pub(super) fn is_valid(name: &str) -> bool {
+  parse(name).is_ok()
+}
+
+// fills the placeholders of name (like `{app}.conf`) from ctx, fails if a key
+// isn't in ctx or if the filled name isn't a valid file name
+pub(super) fn fill(name: &str, ctx: &ShapeContext) -> error::Result<String> {
+  let mut res = String::new();
+  for (is_key, part) in parse(name)? {
+    if !is_key {
+      res.push_str(&part);
+      continue;
+    }
+    match ctx.get(&part) {
+      Some(value) => res.push_str(value),
+      None => {
+        return Err(invalid_name(
+          name,
+          &format!("no value for the placeholder \"{}\"", part),
+        ))
+      }
+    }
+  }
+  if res.is_empty() || res == "." || res == ".." || res.contains(['/', '\\', '\0']) {
+    return Err(Error::new2(
+      ErrorKind::InvalidFileName,
+      format!(
+        "\"{}\" filled to \"{}\" which isn't a valid file name",
+        name, res
+      ),
+    ));
+  }
+  Ok(res)
+}
+
+// maps the names of every entry in schema, patterns are left as they are
+// since `{a,b}` is an alternation in a glob
+fn map_names(
+  schema: &[ShapeItem],
+  f: &mut dyn FnMut(&str) -> error::Result<String>,
+) -> error::Result<ShapeSchema> {
+  let mut res = vec![];
+  for item in schema {
+    res.push(match item {
+      ShapeItem::File(identifier, name, options) => {
+        ShapeItem::File(identifier.clone(), f(name)?, options.clone())
+      }
+      ShapeItem::DirectoryPattern(identifier, name, pattern, options) => {
+        ShapeItem::DirectoryPattern(
+          identifier.clone(),
+          f(name)?,
+          pattern.clone(),
+          options.clone(),
+        )
+      }
+      ShapeItem::DirectorySchema(identifier, name, schema, options) => ShapeItem::DirectorySchema(
+        identifier.clone(),
+        f(name)?,
+        map_names(schema, f)?,
+        options.clone(),
+      ),
+      ShapeItem::DirectorySchemaPattern(identifier, pattern, schema, options) => {
+        ShapeItem::DirectorySchemaPattern(
+          identifier.clone(),
+          pattern.clone(),
+          map_names(schema, f)?,
+          options.clone(),
+        )
+      }
+      ShapeItem::Rest(rest) => ShapeItem::Rest(rest.clone()),
+      ShapeItem::Variants(variants) => {
+        let mut mapped = vec![];
+        for (name, schema) in variants {
+          mapped.push((name.clone(), map_names(schema, f)?));
+        }
+        ShapeItem::Variants(mapped)
+      }
+    });
+  }
+  Ok(res)
+}
+
+// fills the names of every entry in schema from ctx
+pub(super) fn resolve(schema: &[ShapeItem], ctx: &ShapeContext) -> error::Result<ShapeSchema> {
+  map_names(schema, &mut |name| fill(name, ctx))
+}
+
+// the names of schema as they're used on disk by the methods without a context,
+// `{{` and `}}` are unescaped and a placeholder is an error
+pub(super) fn literal(schema: &[ShapeItem]) -> error::Result<ShapeSchema> {
+  resolve(schema, &ShapeContext::new())
+}
+
+// escapes the braces in the names of schema, so they're used as they are
+pub(super) fn escape(schema: &[ShapeItem]) -> ShapeSchema {
+  let escaped = map_names(schema, &mut |name| {
+    Ok(name.replace('{', "{{").replace('}', "}}"))
+  });
+  escaped.expect("escaping a name can't fail")
+}
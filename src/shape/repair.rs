@@ -0,0 +1,330 @@
+#[cfg(unix)]
+use super::mode;
+use super::{
+  closest_variant, matching_dirs, validate_report, EntryType, ModeRequirement, ShapeItem,
+  ViolationKind,
+};
+use crate::dir::Dir;
+use crate::error::{self, Error, ErrorKind};
+use crate::pattern::Pattern;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// what `Shape::repair` does with entries that violate the shape
+/// (files that don't match a pattern, undeclared entries and entries of the wrong type)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolatingEntries {
+  /// leave them as they are (the default)
+  Keep,
+  /// delete them
+  Delete,
+  /// move them to the directory, keeping their path relative to the repaired directory
+  ///
+  /// a relative path is relative to the repaired directory, the directory (and the
+  /// folders containing it) are never moved themselves
+  MoveTo(PathBuf),
+}
+
+/// options used by `Shape::repair`
+#[derive(Debug, Clone)]
+pub struct RepairPolicy {
+  /// create missing files and folders (default: true)
+  pub create_missing: bool,
+  /// create missing optional entries too (default: false)
+  pub create_optional: bool,
+  /// what to do with entries that violate the shape (default: Keep)
+  pub violating: ViolatingEntries,
+  /// don't touch the disk, only report the planned actions (default: false)
+  pub dry_run: bool,
+}
+
+impl RepairPolicy {
+  /// creates the default policy
+  pub fn new() -> Self {
+    RepairPolicy {
+      create_missing: true,
+      create_optional: false,
+      violating: ViolatingEntries::Keep,
+      dry_run: false,
+    }
+  }
+}
+
+impl Default for RepairPolicy {
+  fn default() -> Self {
+    RepairPolicy::new()
+  }
+}
+
+/// an action taken (or planned in a dry run) by `Shape::repair`
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum RepairAction {
+  /// creates a file with it's declared content (if any)
+  CreateFile(PathBuf),
+  CreateDir(PathBuf),
+  Delete(PathBuf),
+  Move(PathBuf, PathBuf),
+  /// changes the permissions of an entry to meet the requirement (only on unix)
+  SetMode(PathBuf, ModeRequirement),
+}
+
+impl fmt::Display for RepairAction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RepairAction::CreateFile(path) => write!(f, "create file {}", path.display()),
+      RepairAction::CreateDir(path) => write!(f, "create folder {}", path.display()),
+      RepairAction::Delete(path) => write!(f, "delete {}", path.display()),
+      RepairAction::Move(from, to) => {
+        write!(f, "move {} to {}", from.display(), to.display())
+      }
+      RepairAction::SetMode(path, requirement) => {
+        write!(f, "set the mode of {} to {}", path.display(), requirement)
+      }
+    }
+  }
+}
+
+/// the actions taken by `Shape::repair`, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairLog {
+  /// every action taken (or planned if `dry_run`)
+  pub actions: Vec<RepairAction>,
+  /// true if the actions were only planned
+  pub dry_run: bool,
+  /// the declared entries that exist with the wrong type and were kept (see
+  /// `ViolatingEntries::Keep`), they can't be repaired without removing them
+  pub unrepairable: Vec<PathBuf>,
+}
+
+impl fmt::Display for RepairLog {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for action in &self.actions {
+      if self.dry_run {
+        write!(f, "(dry run) ")?;
+      }
+      writeln!(f, "{}", action)?;
+    }
+    for path in &self.unrepairable {
+      writeln!(f, "can't repair {}, it has the wrong type", path.display())?;
+    }
+    Ok(())
+  }
+}
+
+struct Repairer<'a> {
+  root: &'a Path,
+  target: &'a [ShapeItem],
+  policy: &'a RepairPolicy,
+  log: RepairLog,
+  removed: HashSet<PathBuf>,
+}
+
+impl Repairer<'_> {
+  fn run(&mut self, action: RepairAction, content: Option<&[u8]>) -> error::Result<()> {
+    if !self.policy.dry_run {
+      let (res, path) = match &action {
+        RepairAction::CreateFile(path) => (fs::write(path, content.unwrap_or(&[])), path),
+        RepairAction::CreateDir(path) => (fs::create_dir(path), path),
+        RepairAction::Delete(path) if path.is_dir() => (fs::remove_dir_all(path), path),
+        RepairAction::Delete(path) => (fs::remove_file(path), path),
+        RepairAction::Move(from, to) => {
+          let res = match to.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+          };
+          (res.and_then(|_| fs::rename(from, to)), from)
+        }
+        RepairAction::SetMode(path, requirement) => {
+          #[cfg(unix)]
+          mode::set_mode(path, requirement.apply(mode::mode_of(path)?))?;
+          #[cfg(not(unix))]
+          let _ = requirement;
+          (Ok(()), path)
+        }
+      };
+      error::result_from_io(res).map_err(|e| e.set_path(path.clone()))?;
+    }
+    self.log.actions.push(action);
+    Ok(())
+  }
+  fn exists(&self, path: &Path) -> bool {
+    path.exists() && !self.removed.contains(path)
+  }
+  // the folder violating entries are moved to, relative paths are relative to the root
+  fn move_to(&self) -> Option<PathBuf> {
+    match &self.policy.violating {
+      ViolatingEntries::MoveTo(to) if to.is_relative() => Some(self.root.join(to)),
+      ViolatingEntries::MoveTo(to) => Some(to.clone()),
+      _ => None,
+    }
+  }
+  // applies the mode requirement of an entry that was just created (or will be in a
+  // dry run) or that exists with the right type and doesn't meet it
+  fn set_mode(
+    &mut self,
+    path: &Path,
+    requirement: &ModeRequirement,
+    entry_type: EntryType,
+    created: bool,
+  ) -> error::Result<()> {
+    if requirement.is_empty() {
+      return Ok(());
+    }
+    #[cfg(unix)]
+    {
+      if !created
+        && (EntryType::of(path) != Some(entry_type) || requirement.is_met(mode::mode_of(path)?))
+      {
+        return Ok(());
+      }
+      self.run(
+        RepairAction::SetMode(path.to_path_buf(), *requirement),
+        None,
+      )
+    }
+    #[cfg(not(unix))]
+    {
+      let _ = (path, entry_type, created);
+      Ok(())
+    }
+  }
+  fn remove_violating(&mut self) -> error::Result<()> {
+    let report = validate_report(self.root, self.target);
+    let move_to = self.move_to();
+    for violation in report.violations {
+      match violation.kind {
+        ViolationKind::WrongType { .. }
+        | ViolationKind::PatternMismatch { .. }
+        | ViolationKind::UnexpectedEntry { .. } => {}
+        _ => continue,
+      }
+      let action = match &move_to {
+        _ if self.policy.violating == ViolatingEntries::Keep => continue,
+        // the folder entries are moved to isn't moved itself when it's in the root
+        Some(to) if to.starts_with(&violation.path) || violation.path.starts_with(to) => continue,
+        Some(to) => {
+          let relative = violation
+            .path
+            .strip_prefix(self.root)
+            .unwrap_or(&violation.path);
+          RepairAction::Move(violation.path.clone(), to.join(relative))
+        }
+        None => RepairAction::Delete(violation.path.clone()),
+      };
+      self.run(action, None)?;
+      self.removed.insert(violation.path);
+    }
+    Ok(())
+  }
+  fn create_missing(
+    &mut self,
+    path: &Path,
+    target: &[ShapeItem],
+    exists: bool,
+  ) -> error::Result<()> {
+    for item in target {
+      if let Some(name) = item.name() {
+        let entry = path.join(name);
+        let entry_exists = exists && self.exists(&entry);
+        if !entry_exists && item.options().optional && !self.policy.create_optional {
+          continue;
+        }
+        let entry_type = match item {
+          ShapeItem::File(_, _, _) => EntryType::File,
+          _ => EntryType::Dir,
+        };
+        // a kept entry of the wrong type is left as it is, nothing can be created in it
+        if entry_exists && EntryType::of(&entry) != Some(entry_type) {
+          self.log.unrepairable.push(entry);
+          continue;
+        }
+        match item {
+          ShapeItem::File(_, _, options) => {
+            if !entry_exists {
+              let content = options.content.as_deref();
+              self.run(RepairAction::CreateFile(entry.clone()), content)?;
+            }
+            self.set_mode(&entry, &options.mode, EntryType::File, !entry_exists)?;
+          }
+          ShapeItem::DirectoryPattern(_, _, _, options) => {
+            if !entry_exists {
+              self.run(RepairAction::CreateDir(entry.clone()), None)?;
+            }
+            self.set_mode(&entry, &options.mode, EntryType::Dir, !entry_exists)?;
+          }
+          ShapeItem::DirectorySchema(_, _, schema, options) => {
+            if !entry_exists {
+              self.run(RepairAction::CreateDir(entry.clone()), None)?;
+            }
+            self.create_missing(&entry, schema, entry_exists)?;
+            // applied after the children are created, a readonly folder can't get new entries
+            self.set_mode(&entry, &options.mode, EntryType::Dir, !entry_exists)?;
+          }
+          _ => {}
+        }
+      } else if let ShapeItem::DirectorySchemaPattern(_, pattern, schema, _) = item {
+        if !exists {
+          continue;
+        }
+        let dir = Dir::new(path)?;
+        for sub_dir in matching_dirs(&dir, target, &Pattern::new(pattern)?)? {
+          if self.exists(&sub_dir.path) {
+            self.create_missing(&sub_dir.path, schema, true)?;
+          }
+        }
+      } else if let ShapeItem::Variants(variants) = item {
+        // the closest variant is completed, the first one in a new folder
+        let i = if exists {
+          closest_variant(&Dir::new(path)?, variants, "").0
+        } else {
+          0
+        };
+        if let Some((_, schema)) = variants.get(i) {
+          self.create_missing(path, schema, exists)?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+pub(super) fn repair<P: AsRef<Path>>(
+  path: P,
+  target: &[ShapeItem],
+  policy: &RepairPolicy,
+) -> error::Result<RepairLog> {
+  let root = path.as_ref();
+  let mut repairer = Repairer {
+    root,
+    target,
+    policy,
+    log: RepairLog {
+      actions: vec![],
+      dry_run: policy.dry_run,
+      unrepairable: vec![],
+    },
+    removed: HashSet::new(),
+  };
+  let exists = match EntryType::of(root) {
+    Some(EntryType::Dir) => {
+      repairer.remove_violating()?;
+      true
+    }
+    Some(EntryType::File) => {
+      return Err(
+        Error::new(ErrorKind::InvalidFolder, "can't repair a file").set_path(root.to_path_buf()),
+      )
+    }
+    None => false,
+  };
+  if policy.create_missing {
+    if !exists {
+      repairer.run(RepairAction::CreateDir(root.to_path_buf()), None)?;
+    }
+    repairer.create_missing(root, target, exists)?;
+  }
+  Ok(repairer.log)
+}
@@ -0,0 +1,352 @@
+use super::ModeRequirement;
+use crate::error::{Error, ErrorKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// the type of an entry in a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum EntryType {
+  File,
+  Dir,
+}
+
+impl EntryType {
+  /// returns the type of the entry at path or None if it doesn't exist
+  pub fn of<P: AsRef<Path>>(path: P) -> Option<EntryType> {
+    let path = path.as_ref();
+    if path.is_dir() {
+      Some(EntryType::Dir)
+    } else if path.exists() {
+      Some(EntryType::File)
+    } else {
+      None
+    }
+  }
+  pub(super) fn as_str(self) -> &'static str {
+    match self {
+      EntryType::File => "file",
+      EntryType::Dir => "folder",
+    }
+  }
+}
+
+/// why an entry doesn't match the shape
+#[derive(Debug)]
+pub enum ViolationKind {
+  /// a declared file doesn't exist
+  MissingFile,
+  /// a declared folder doesn't exist
+  MissingDir,
+  /// a declared entry exists but it's a file instead of a folder or the opposite,
+  /// also used for folders found in a pattern dir
+  WrongType {
+    /// the type the shape expects
+    expected: EntryType,
+    /// the type found on disk
+    found: EntryType,
+  },
+  /// a file in a pattern dir doesn't match the pattern
+  PatternMismatch {
+    /// the pattern of the dir
+    pattern: String,
+  },
+  /// an undeclared entry that the rest of the directory doesn't allow
+  UnexpectedEntry {
+    /// the type of the entry
+    entry_type: EntryType,
+    /// the rest pattern it doesn't match, None if rest is forbidden
+    rest_pattern: Option<String>,
+  },
+  /// the permissions of an entry don't meet it's `ModeRequirement`
+  WrongMode {
+    /// the type of the entry
+    entry_type: EntryType,
+    /// the requirement of the shape
+    expected: ModeRequirement,
+    /// the permission bits found on disk
+    actual: u32,
+  },
+  /// the content of a file failed one of it's `ContentValidator`s
+  InvalidContent(String),
+  /// the entry couldn't be checked, because it couldn't be read
+  /// or the pattern of the schema is invalid
+  Unreadable(Error),
+}
+
+/// a single reason why a directory doesn't match a shape
+#[derive(Debug)]
+pub struct Violation {
+  /// why the entry doesn't match
+  pub kind: ViolationKind,
+  /// the path of the entry
+  pub path: PathBuf,
+  /// the path of the schema entry that produced the violation, like `hi_dir.hi`
+  ///
+  /// directories matched by a pattern are written as `tenants[tenant-a]` and
+  /// the rest of a directory as `hi_dir.__rest`, it's empty for the root directory
+  pub identifier: String,
+}
+
+impl Violation {
+  /// creates a violation
+  pub fn new<S: Into<String>>(kind: ViolationKind, path: PathBuf, identifier: S) -> Self {
+    Violation {
+      kind,
+      path,
+      identifier: identifier.into(),
+    }
+  }
+  /// a short human readable description of the violation
+  pub fn message(&self) -> String {
+    match &self.kind {
+      ViolationKind::MissingFile => "missing file".to_string(),
+      ViolationKind::MissingDir => "missing folder".to_string(),
+      ViolationKind::WrongType { expected, found } => format!(
+        "expected a {} but found a {}",
+        expected.as_str(),
+        found.as_str()
+      ),
+      ViolationKind::PatternMismatch { pattern } => {
+        format!("file doesn't match pattern \"{}\"", pattern)
+      }
+      ViolationKind::UnexpectedEntry {
+        entry_type,
+        rest_pattern: Some(pattern),
+      } => format!(
+        "undeclared {} doesn't match rest pattern \"{}\"",
+        entry_type.as_str(),
+        pattern
+      ),
+      ViolationKind::UnexpectedEntry {
+        entry_type,
+        rest_pattern: None,
+      } => format!("undeclared {} is not allowed", entry_type.as_str()),
+      ViolationKind::WrongMode {
+        expected, actual, ..
+      } => format!("mode is {:04o}, expected {}", actual, expected),
+      ViolationKind::InvalidContent(message) => message.clone(),
+      ViolationKind::Unreadable(e) => e.message.clone(),
+    }
+  }
+  /// converts the violation to an error like the ones returned by `Shape::validate`
+  pub fn into_error(self) -> Error {
+    let message = self.message();
+    let error = match self.kind {
+      ViolationKind::MissingFile | ViolationKind::MissingDir => {
+        Error::new_from_kind(ErrorKind::NotFound)
+      }
+      ViolationKind::WrongType { found, .. }
+      | ViolationKind::UnexpectedEntry {
+        entry_type: found, ..
+      }
+      | ViolationKind::WrongMode {
+        entry_type: found, ..
+      } => match found {
+        EntryType::File => Error::new2(ErrorKind::InvalidFile, message),
+        EntryType::Dir => Error::new2(ErrorKind::InvalidFolder, message),
+      },
+      ViolationKind::PatternMismatch { .. } | ViolationKind::InvalidContent(_) => {
+        Error::new2(ErrorKind::InvalidFile, message)
+      }
+      ViolationKind::Unreadable(e) if e.path.is_some() => return e,
+      ViolationKind::Unreadable(e) => e,
+    };
+    error.set_path(self.path)
+  }
+  fn kind_name(&self) -> &'static str {
+    match self.kind {
+      ViolationKind::MissingFile => "missing_file",
+      ViolationKind::MissingDir => "missing_dir",
+      ViolationKind::WrongType { .. } => "wrong_type",
+      ViolationKind::PatternMismatch { .. } => "pattern_mismatch",
+      ViolationKind::UnexpectedEntry { .. } => "unexpected_entry",
+      ViolationKind::WrongMode { .. } => "wrong_mode",
+      ViolationKind::InvalidContent(_) => "invalid_content",
+      ViolationKind::Unreadable(_) => "unreadable",
+    }
+  }
+}
+
+/// the result of validating a directory against a shape, see `Shape::validate_report`
+/// ```no_run
+/// # use fs_pro::{File, Shape};
+/// # #[derive(Shape)]
+/// # struct MyShapedDir {
+/// #   #[name = "my_file.txt"]
+/// #   pub my_file: File,
+/// # }
+/// # let shape: Shape<MyShapedDir> = Shape::new();
+/// let report = shape.validate_report("target");
+/// if !report.is_valid() {
+///   println!("{}", report); // same as report.render_tree()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ValidationReport {
+  /// the validated directory
+  pub root: PathBuf,
+  /// every violation found, in the order of the schema
+  pub violations: Vec<Violation>,
+}
+
+#[derive(Default)]
+struct TreeNode<'a> {
+  children: BTreeMap<String, TreeNode<'a>>,
+  violations: Vec<&'a Violation>,
+}
+
+impl<'a> TreeNode<'a> {
+  fn render(&self, prefix: &str, out: &mut String) {
+    let last_index = self.children.len().saturating_sub(1);
+    for (i, (name, child)) in self.children.iter().enumerate() {
+      let (branch, indent) = if i == last_index {
+        ("└── ", "    ")
+      } else {
+        ("├── ", "│   ")
+      };
+      out.push_str(prefix);
+      out.push_str(branch);
+      out.push_str(name);
+      if !child.children.is_empty() {
+        out.push('/');
+      }
+      render_violations(&child.violations, out);
+      out.push('\n');
+      child.render(&format!("{}{}", prefix, indent), out);
+    }
+  }
+}
+
+fn render_violations(violations: &[&Violation], out: &mut String) {
+  for (i, violation) in violations.iter().enumerate() {
+    out.push_str(if i == 0 { "  ✗ " } else { "; " });
+    out.push_str(&violation.message());
+    if !violation.identifier.is_empty() {
+      out.push_str(&format!(" [{}]", violation.identifier));
+    }
+  }
+}
+
+impl ValidationReport {
+  /// creates an empty report
+  pub fn new(root: PathBuf) -> Self {
+    ValidationReport {
+      root,
+      violations: vec![],
+    }
+  }
+  /// returns true if no violations were found
+  pub fn is_valid(&self) -> bool {
+    self.violations.is_empty()
+  }
+  /// converts the report to the result returned by `Shape::validate`
+  pub fn into_result(self) -> Result<(), Vec<Error>> {
+    if self.is_valid() {
+      return Ok(());
+    }
+    Err(
+      self
+        .violations
+        .into_iter()
+        .map(Violation::into_error)
+        .collect(),
+    )
+  }
+  /// renders the violations as a tree of the directory
+  /// ```text
+  /// target/
+  /// ├── hi_dir/
+  /// │   └── hi  ✗ missing file [hi_dir.hi]
+  /// └── some.txt  ✗ undeclared file is not allowed [__rest]
+  /// ```
+  pub fn render_tree(&self) -> String {
+    let mut root = TreeNode::default();
+    for violation in &self.violations {
+      let relative = violation
+        .path
+        .strip_prefix(&self.root)
+        .unwrap_or(&violation.path);
+      let mut node = &mut root;
+      for component in relative.components() {
+        let name = component.as_os_str().to_string_lossy().to_string();
+        node = node.children.entry(name).or_default();
+      }
+      node.violations.push(violation);
+    }
+    let mut out = format!("{}/", self.root.display());
+    if self.is_valid() {
+      out.push_str("  ✓ matches the shape");
+    }
+    render_violations(&root.violations, &mut out);
+    out.push('\n');
+    root.render("", &mut out);
+    out
+  }
+  /// renders the report as json
+  /// ```json
+  /// {
+  ///   "root": "target",
+  ///   "valid": false,
+  ///   "violations": [
+  ///     {
+  ///       "kind": "missing_file",
+  ///       "path": "target/hi_dir/hi",
+  ///       "identifier": "hi_dir.hi",
+  ///       "message": "missing file"
+  ///     }
+  ///   ]
+  /// }
+  /// ```
+  /// `wrong_type` violations also have `expected` and `found`, `pattern_mismatch`
+  /// have `pattern`, `unexpected_entry` have `entry_type` and `rest_pattern` and
+  /// `wrong_mode` have `expected` and `actual` (like `"0600"`)
+  #[cfg(feature = "json")]
+  pub fn to_json(&self) -> String {
+    use serde_json::{json, Value};
+
+    let violations: Vec<Value> = self
+      .violations
+      .iter()
+      .map(|violation| {
+        let mut value = json!({
+          "kind": violation.kind_name(),
+          "path": violation.path.to_string_lossy(),
+          "identifier": violation.identifier,
+          "message": violation.message(),
+        });
+        let extra = match &violation.kind {
+          ViolationKind::WrongType { expected, found } => {
+            json!({ "expected": expected.as_str(), "found": found.as_str() })
+          }
+          ViolationKind::PatternMismatch { pattern } => json!({ "pattern": pattern }),
+          ViolationKind::WrongMode {
+            expected, actual, ..
+          } => json!({ "expected": expected.to_string(), "actual": format!("{:04o}", actual) }),
+          ViolationKind::UnexpectedEntry {
+            entry_type,
+            rest_pattern,
+          } => json!({ "entry_type": entry_type.as_str(), "rest_pattern": rest_pattern }),
+          _ => json!({}),
+        };
+        if let (Value::Object(value), Value::Object(extra)) = (&mut value, extra) {
+          value.extend(extra);
+        }
+        value
+      })
+      .collect();
+    let report = json!({
+      "root": self.root.to_string_lossy(),
+      "valid": self.is_valid(),
+      "violations": violations,
+    });
+    serde_json::to_string_pretty(&report).unwrap()
+  }
+}
+
+impl fmt::Display for ValidationReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.render_tree())
+  }
+}
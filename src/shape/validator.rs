@@ -0,0 +1,153 @@
+use crate::error;
+use crate::file::File;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// a check of the content of a file, they're set on `File` fields with `#[max_size = 1024]`,
+/// `#[min_size = 1]`, `#[non_empty]`, `#[utf8]`, `#[json]` or `#[validate_with = "path::to::fn"]`
+/// and evaluated by `Shape::validate`
+///
+/// `#[json]` needs the `json` feature, without it the derive fails to compile:
+/// ```compile_fail
+/// # #[cfg(feature = "json")]
+/// # compile_error!("the json feature is enabled");
+/// use fs_pro::{File, Shape};
+///
+/// #[derive(Shape)]
+/// struct Config {
+///   #[name = "config.json"]
+///   #[json]
+///   pub config: File,
+/// }
+/// ```
+///
+/// example:
+/// ```
+/// use fs_pro::{File, Shape};
+///
+/// fn is_toml(file: &File) -> fs_pro::error::Result<()> {
+///   // ...
+///   # Ok(())
+/// }
+///
+/// #[derive(Shape)]
+/// struct Project {
+///   #[name = "config.toml"]
+///   #[non_empty]
+///   #[max_size = 4096]
+///   #[validate_with = "is_toml"]
+///   pub config: File,
+/// }
+/// ```
+#[derive(Clone)]
+pub enum ContentValidator {
+  /// the file is at most this many bytes
+  MaxSize(u64),
+  /// the file is at least this many bytes
+  MinSize(u64),
+  /// the file isn't empty
+  NonEmpty,
+  /// the file is valid utf-8
+  Utf8,
+  /// the file is valid json
+  #[cfg(feature = "json")]
+  Json,
+  /// the function returns Ok for the file, the message of the error is reported
+  With(fn(&File) -> error::Result<()>),
+}
+
+// the validator `#[json]` sets, used by `#[derive(Shape)]` so a missing feature
+// gets a clear error instead of a missing variant
+#[cfg(feature = "json")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_validator {
+  () => {
+    $crate::shape::ContentValidator::Json
+  };
+}
+
+#[cfg(not(feature = "json"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __json_validator {
+  () => {
+    compile_error!("#[json] requires the `json` feature of fs_pro")
+  };
+}
+
+impl fmt::Debug for ContentValidator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ContentValidator::MaxSize(size) => write!(f, "MaxSize({})", size),
+      ContentValidator::MinSize(size) => write!(f, "MinSize({})", size),
+      ContentValidator::NonEmpty => write!(f, "NonEmpty"),
+      ContentValidator::Utf8 => write!(f, "Utf8"),
+      #[cfg(feature = "json")]
+      ContentValidator::Json => write!(f, "Json"),
+      ContentValidator::With(_) => write!(f, "With(fn)"),
+    }
+  }
+}
+
+impl PartialEq for ContentValidator {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (ContentValidator::MaxSize(a), ContentValidator::MaxSize(b))
+      | (ContentValidator::MinSize(a), ContentValidator::MinSize(b)) => a == b,
+      (ContentValidator::NonEmpty, ContentValidator::NonEmpty)
+      | (ContentValidator::Utf8, ContentValidator::Utf8) => true,
+      #[cfg(feature = "json")]
+      (ContentValidator::Json, ContentValidator::Json) => true,
+      // two validators are the same function if they have the same address
+      (ContentValidator::With(a), ContentValidator::With(b)) => *a as usize == *b as usize,
+      _ => false,
+    }
+  }
+}
+
+impl ContentValidator {
+  /// checks the file at path, returns the reason it's invalid
+  pub fn check(&self, path: &Path) -> error::Result<Option<String>> {
+    let size = || -> error::Result<u64> {
+      let metadata = error::result_from_io(fs::metadata(path))?;
+      Ok(metadata.len())
+    };
+    let read = || error::result_from_io(fs::read(path));
+    let res = match self {
+      ContentValidator::MaxSize(max) => {
+        let size = size()?;
+        if size > *max {
+          Some(format!("file is {} bytes, more than {} bytes", size, max))
+        } else {
+          None
+        }
+      }
+      ContentValidator::MinSize(min) => {
+        let size = size()?;
+        if size < *min {
+          Some(format!("file is {} bytes, less than {} bytes", size, min))
+        } else {
+          None
+        }
+      }
+      ContentValidator::NonEmpty if size()? == 0 => Some("file is empty".to_string()),
+      ContentValidator::NonEmpty => None,
+      ContentValidator::Utf8 => match String::from_utf8(read()?) {
+        Ok(_) => None,
+        Err(e) => Some(format!("file is not valid utf-8: {}", e.utf8_error())),
+      },
+      #[cfg(feature = "json")]
+      ContentValidator::Json => match serde_json::from_slice::<serde_json::Value>(&read()?) {
+        Ok(_) => None,
+        Err(e) => Some(format!("file is not valid json: {}", e)),
+      },
+      ContentValidator::With(validate) => match validate(&File::new(path)?) {
+        Ok(()) => None,
+        Err(e) => Some(e.message),
+      },
+    };
+    Ok(res)
+  }
+}
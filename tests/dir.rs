@@ -235,3 +235,89 @@ fn read() -> error::Result<()> {
   okay_to_err(dir.delete());
   Ok(())
 }
+
+#[test]
+fn lock() -> error::Result<()> {
+  let dir = Dir::temp_dir_rand()?;
+  let lock = dir.lock()?;
+  let lockfile = dir.get_file(".lock")?;
+  assert_eq!(lock.path(), lockfile.path.as_path());
+  let content = lockfile.read_to_string()?;
+  assert!(content.starts_with(&std::process::id().to_string()));
+  assert!(dir.try_lock()?.is_none());
+  // locking it again in the same process only waits until the timeout
+  let timeout = std::time::Duration::from_millis(120);
+  assert!(dir.lock_timeout(timeout)?.is_none());
+  drop(lock);
+  assert!(!lockfile.exists());
+
+  // two locks never have the same owner, even if they are taken in the same second
+  let lock = dir.lock_timeout(timeout)?.unwrap();
+  let first = lockfile.read_to_string()?;
+  lock.unlock()?;
+  let lock = dir.lock()?;
+  assert_ne!(lockfile.read_to_string()?, first);
+  drop(lock);
+
+  // a lockfile left by a process that exited long ago is taken over
+  lockfile.write("999999999 1000000000\n")?;
+  let lock = dir.try_lock()?.unwrap();
+  assert!(lockfile
+    .read_to_string()?
+    .starts_with(&std::process::id().to_string()));
+  lock.unlock()?;
+  assert!(!lockfile.exists());
+  assert_eq!(dir.read()?.len(), 0);
+  okay_to_err(dir.delete());
+  Ok(())
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn manifest() -> error::Result<()> {
+  use fs_pro::hash::Algorithm;
+  use std::path::PathBuf;
+
+  let dir = Dir::temp_dir_rand()?;
+  dir.create_file("a.txt")?.write("a")?;
+  dir.create_file("b.txt")?.write("b")?;
+  dir.create_dir("sub")?.create_file("c.txt")?.write("c")?;
+  let manifest = dir.write_manifest("SHA256SUMS", Algorithm::Sha256)?;
+  let content = manifest.read_to_string()?;
+  let names: Vec<_> = content
+    .lines()
+    .map(|line| line.split("  ").nth(1).unwrap())
+    .collect();
+  assert_eq!(names, vec!["a.txt", "b.txt", "sub/c.txt"]);
+  assert!(content
+    .starts_with("ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb  a.txt\n"));
+  assert!(dir.verify_manifest("SHA256SUMS")?.is_valid());
+
+  dir.get_file("a.txt")?.write("changed")?;
+  dir.delete_file("b.txt")?;
+  dir.create_file("d.txt")?;
+  let report = dir.verify_manifest("SHA256SUMS")?;
+  assert!(!report.is_valid());
+  assert_eq!(report.mismatched, vec![PathBuf::from("a.txt")]);
+  assert_eq!(report.missing, vec![PathBuf::from("b.txt")]);
+  assert_eq!(report.extra, vec![PathBuf::from("d.txt")]);
+
+  // binary mode and "./" prefixes written by sha256sum are read too
+  let other = dir.create_file("other.sums")?;
+  other.write(content.replace("  sub/", " *./sub/"))?;
+  let report = dir.verify_manifest("other.sums")?;
+  assert_eq!(report.missing, vec![PathBuf::from("b.txt")]);
+  assert!(report.extra.contains(&PathBuf::from("SHA256SUMS")));
+
+  dir.write_manifest("B3SUMS", Algorithm::Blake3)?;
+  // the other manifests are listed too
+  assert!(dir
+    .verify_manifest_with("B3SUMS", Algorithm::Blake3)?
+    .is_valid());
+
+  other.write("not a manifest\n")?;
+  let err = dir.verify_manifest("other.sums").err().unwrap();
+  assert!(matches!(err.kind, error::ErrorKind::InvalidFile));
+  okay_to_err(dir.delete());
+  Ok(())
+}
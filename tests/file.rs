@@ -148,6 +148,39 @@ fn write() {
   okay_to_err(file.delete());
 }
 
+#[test]
+fn write_atomic() -> error::Result<()> {
+  let dir = Dir::temp_dir_rand()?;
+  let file = dir.get_file("config.toml")?;
+  file.write_atomic("a = 1")?;
+  assert_eq!(file.read_to_string()?, "a = 1");
+  file.write_atomic("a = 2")?;
+  assert_eq!(file.read_to_string()?, "a = 2");
+  // the temp file is renamed over the file
+  assert_eq!(dir.read()?.len(), 1);
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(&file.path, fs::Permissions::from_mode(0o600)).unwrap();
+    file.write_atomic("a = 3")?;
+    let mode = file.metadata()?.permissions().mode() & 0o777;
+    assert_eq!(mode, 0o600);
+
+    let link = dir.path.join("link.toml");
+    std::os::unix::fs::symlink(&file.path, &link).unwrap();
+    File::new(&link)?.write_atomic("a = 4")?;
+    assert!(fs::symlink_metadata(&link)
+      .unwrap()
+      .file_type()
+      .is_symlink());
+    assert_eq!(file.read_to_string()?, "a = 4");
+  }
+  okay_to_err(dir.delete());
+  Ok(())
+}
+
 #[test]
 fn read() {
   let file = File::temp_file_rand().unwrap();
@@ -270,3 +303,100 @@ fn json() -> error::Result<()> {
   assert_eq!(json["hello"], String::from("world"));
   Ok(())
 }
+
+#[test]
+fn buffered_io() -> error::Result<()> {
+  use std::io::{Read, Write};
+
+  let dir = Dir::temp_dir_rand()?;
+  let file = dir.get_file("data.txt")?;
+  let mut writer = file.writer()?;
+  write!(writer, "one\r\ntwo\nthree").unwrap();
+  writer.flush().unwrap();
+  drop(writer);
+
+  let lines = file.lines()?.collect::<error::Result<Vec<_>>>()?;
+  assert_eq!(lines, vec!["one", "two", "three"]);
+
+  let mut content = String::new();
+  file.reader()?.read_to_string(&mut content).unwrap();
+  assert_eq!(content, "one\r\ntwo\nthree");
+
+  let chunks = file.chunks(6)?.collect::<error::Result<Vec<_>>>()?;
+  assert_eq!(
+    chunks,
+    vec![b"one\r\nt".to_vec(), b"wo\nthr".to_vec(), b"ee".to_vec()]
+  );
+  assert!(file.chunks(0).is_err());
+
+  let missing = dir.get_file("missing.txt")?;
+  let err = missing.lines().err().unwrap();
+  assert!(matches!(err.kind, error::ErrorKind::NotFound));
+  assert_eq!(err.path, Some(missing.path.clone()));
+  okay_to_err(dir.delete());
+  Ok(())
+}
+
+#[test]
+fn lock() -> error::Result<()> {
+  let dir = Dir::temp_dir_rand()?;
+  let file = dir.get_file("state.json")?;
+  let exclusive = file.lock_exclusive()?;
+  // the file is created to be locked
+  assert!(file.exists());
+  assert!(file.try_lock_exclusive()?.is_none());
+  assert!(file.try_lock_shared()?.is_none());
+  exclusive.unlock()?;
+
+  let shared = file.lock_shared()?;
+  let other = file.try_lock_shared()?;
+  assert!(other.is_some());
+  assert!(file.try_lock_exclusive()?.is_none());
+  drop(shared);
+  drop(other);
+  assert!(file.try_lock_exclusive()?.is_some());
+  okay_to_err(dir.delete());
+  Ok(())
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn hash() -> error::Result<()> {
+  use fs_pro::hash::{Algorithm, Checksum};
+
+  let dir = Dir::temp_dir_rand()?;
+  let file = dir.get_file("hello.txt")?;
+  file.write("hello")?;
+  let expected = [
+    (
+      Algorithm::Sha256,
+      "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+    ),
+    (Algorithm::Sha1, "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+    (Algorithm::Crc32, "3610a686"),
+  ];
+  for (algorithm, hex) in expected.iter() {
+    assert_eq!(file.hash(*algorithm)?.to_hex(), *hex);
+    assert!(file.verify(&Checksum::from_hex(*algorithm, &hex.to_uppercase())?)?);
+  }
+  let empty = dir.create_file("empty.txt")?;
+  assert_eq!(
+    empty.hash(Algorithm::Blake3)?.to_string(),
+    "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+  );
+  assert!(!empty.verify(&file.hash(Algorithm::Blake3)?)?);
+  assert!(Checksum::from_hex(Algorithm::Crc32, "3610a6").is_err());
+  assert!(Checksum::from_hex(Algorithm::Crc32, "3610a6zz").is_err());
+  assert_eq!("SHA-256".parse::<Algorithm>()?, Algorithm::Sha256);
+
+  let copy = file.copy_verified(dir.path.join("copy.txt"), Algorithm::Sha256)?;
+  assert_eq!(copy.read_to_string()?, "hello");
+  let err = dir
+    .get_file("missing.txt")?
+    .hash(Algorithm::Sha1)
+    .err()
+    .unwrap();
+  assert!(matches!(err.kind, error::ErrorKind::NotFound));
+  okay_to_err(dir.delete());
+  Ok(())
+}
@@ -1,4 +1,13 @@
-use fs_pro::{error, Dir, File, Shape};
+use fs_pro::{
+  error,
+  shape::{
+    schema_from_static, ChangeKind, ContentValidator, CreateOptions, DynShape, EntryType, Migrate,
+    Migration, ShapeBuilder, ShapeContext, ShapeDescribe, ShapeDiff, ShapeEvent, ShapeHookResult,
+    ShapeInstance, ShapeRest, ViolationKind,
+  },
+  Dir, File, Shape,
+};
+use std::collections::HashMap;
 
 fn okay_to_err<T, E>(result: Result<T, E>) {
   match result {
@@ -47,3 +56,1281 @@ fn main_test() -> error::Result<()> {
   okay_to_err(target.delete());
   Ok(())
 }
+
+#[derive(Shape)]
+#[rest = "forbid"]
+struct Strict {
+  #[name = "hi.txt"]
+  pub hi: File,
+  pub hi_dir: H,
+}
+
+#[derive(Shape)]
+#[rest_pattern = "*.md"]
+struct Docs {
+  #[name = "hi.txt"]
+  pub hi: File,
+}
+
+#[test]
+fn rest() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Strict> = Shape::new();
+  let strict = shape.create_at(&target.path)?;
+  assert!(strict.hi.exists());
+  assert!(strict.hi_dir.hi.exists());
+  assert!(shape.validate(&target.path).is_ok());
+
+  target.create_file("stray.txt")?;
+  target.create_dir("stray_dir")?;
+  // only the top level forbids undeclared entries
+  target.get_dir("hi_dir")?.create_file("allowed.txt")?;
+  let mut errors = shape.validate(&target.path).unwrap_err();
+  errors.sort_by(|a, b| a.path.cmp(&b.path));
+  assert_eq!(errors.len(), 2);
+  assert!(matches!(errors[0].kind, error::ErrorKind::InvalidFile));
+  assert_eq!(errors[0].path, Some(target.path.join("stray.txt")));
+  assert!(matches!(errors[1].kind, error::ErrorKind::InvalidFolder));
+  assert_eq!(errors[1].path, Some(target.path.join("stray_dir")));
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn rest_pattern() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Docs> = Shape::new();
+  let docs = shape.create_at(&target.path)?;
+  docs.hi.write("hi")?;
+  target.create_file("README.md")?;
+  assert!(shape.validate(&target.path).is_ok());
+
+  target.create_file("notes.txt")?;
+  let errors = shape.validate(&target.path).unwrap_err();
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].path, Some(target.path.join("notes.txt")));
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct WithOptional {
+  #[name = "hi.txt"]
+  pub hi: File,
+  #[name = "config.toml"]
+  pub config: Option<File>,
+  #[pattern = "*.log"]
+  pub logs: Option<Dir>,
+  pub hi_dir: Option<H>,
+}
+
+#[test]
+fn optional() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<WithOptional> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert!(inst.hi.exists());
+  assert!(inst.config.is_none());
+  assert!(inst.logs.is_none());
+  assert!(inst.hi_dir.is_none());
+  assert!(!target.entry_exists("config.toml"));
+  assert!(shape.validate(&target.path).is_ok());
+
+  // optional entries are still validated when they exist
+  target.create_dir("hi_dir")?;
+  assert_eq!(shape.validate(&target.path).unwrap_err().len(), 1);
+  // and bound when they exist
+  let inst = shape.create_at(&target.path)?;
+  assert!(inst.config.is_none());
+  assert!(inst.hi_dir.unwrap().hi.exists());
+
+  let mut options = CreateOptions::new();
+  options.create_optional = true;
+  let inst = shape.create_at_with_options(&target.path, &options)?;
+  assert_eq!(inst.config.unwrap().path, target.path.join("config.toml"));
+  assert!(inst.logs.unwrap().exists());
+  assert!(inst.hi_dir.unwrap().hi.exists());
+  assert!(shape.validate(&target.path).is_ok());
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn open() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Test> = Shape::new();
+  assert!(shape.open(&target.path).is_err());
+  // nothing is created when the directory doesn't match
+  assert!(!target.entry_exists("hi.txt"));
+
+  shape.create_at(&target.path)?.hi.write("hello")?;
+  let some = shape.open(&target.path).unwrap();
+  assert_eq!(some.hi.read_to_string()?, "hello");
+  assert_eq!(some.my_dir.path, target.path.join("my_dir"));
+  assert_eq!(some.hi_dir.hi.path, target.path.join("hi_dir/hi.txt"));
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+#[rest = "forbid"]
+struct Tenants {
+  #[name = "hi.txt"]
+  pub hi: File,
+  #[pattern = "tenant-*"]
+  pub tenants: Vec<H>,
+  #[pattern = "plugin-*"]
+  pub plugins: HashMap<String, H>,
+}
+
+#[test]
+fn repeated() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Tenants> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert!(inst.hi.exists());
+  assert!(inst.tenants.is_empty());
+  assert!(inst.plugins.is_empty());
+
+  target.create_dir("tenant-b")?.create_file("hi.txt")?;
+  target.create_dir("tenant-a")?.create_file("hi.txt")?;
+  target.create_dir("plugin-x")?.create_file("hi.txt")?;
+  assert!(shape.validate(&target.path).is_ok());
+
+  let inst = shape.open(&target.path).unwrap();
+  assert_eq!(inst.tenants.len(), 2);
+  assert_eq!(inst.tenants[0].hi.path, target.path.join("tenant-a/hi.txt"));
+  assert_eq!(inst.tenants[1].hi.path, target.path.join("tenant-b/hi.txt"));
+  assert_eq!(
+    inst.plugins["plugin-x"].hi.path,
+    target.path.join("plugin-x/hi.txt")
+  );
+
+  // every matching folder is validated
+  target.create_dir("tenant-c")?;
+  let errors = shape.validate(&target.path).unwrap_err();
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].path, Some(target.path.join("tenant-c/hi.txt")));
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct WithContent {
+  #[name = "hi.txt"]
+  #[content = "hello\n\"world\""]
+  pub hi: File,
+  #[name = "config.toml"]
+  #[content_file = "fixtures/default.toml"]
+  pub config: File,
+}
+
+#[test]
+fn content() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<WithContent> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert_eq!(inst.hi.read_to_string()?, "hello\n\"world\"");
+  assert_eq!(inst.config.read_to_string()?, "name = \"fs_pro\"\n");
+
+  // non-empty files are left alone unless overwrite is set
+  inst.hi.write("changed")?;
+  shape.create_at(&target.path)?;
+  assert_eq!(inst.hi.read_to_string()?, "changed");
+  let mut options = CreateOptions::new();
+  options.overwrite = true;
+  shape.create_at_with_options(&target.path, &options)?;
+  assert_eq!(inst.hi.read_to_string()?, "hello\n\"world\"");
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct Globs {
+  #[pattern = "*.txt"]
+  pub txt: Dir,
+  #[pattern = "log-?.{txt,md}"]
+  pub logs: Dir,
+  #[pattern = "[a-c]*"]
+  pub abc: Dir,
+  #[pattern = "!*.tmp"]
+  pub no_tmp: Dir,
+}
+
+#[test]
+fn glob_patterns() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Globs> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  for name in &["a.txt", ".txt"] {
+    inst.txt.create_file(name)?;
+  }
+  for name in &["log-1.txt", "log-a.md"] {
+    inst.logs.create_file(name)?;
+  }
+  for name in &["a", "bcd", "c.txt"] {
+    inst.abc.create_file(name)?;
+  }
+  inst.no_tmp.create_file("a.txt")?;
+  assert!(shape.validate(&target.path).is_ok());
+
+  let invalid = [
+    (&inst.txt, "notes.txt.bak"),
+    (&inst.txt, "txt"),
+    (&inst.logs, "log-10.txt"),
+    (&inst.logs, "log-1.rs"),
+    (&inst.abc, "d.txt"),
+    (&inst.no_tmp, "a.tmp"),
+  ];
+  for (dir, name) in &invalid {
+    dir.create_file(name)?;
+  }
+  let mut errors = shape.validate(&target.path).unwrap_err();
+  errors.sort_by(|a, b| a.path.cmp(&b.path));
+  let mut expected: Vec<_> = invalid
+    .iter()
+    .map(|(dir, name)| Some(dir.path.join(name)))
+    .collect();
+  expected.sort();
+  let actual: Vec<_> = errors.into_iter().map(|e| e.path).collect();
+  assert_eq!(actual, expected);
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn builder() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape = ShapeBuilder::new()
+    .file("a.txt")
+    .content("hello")
+    .dir_pattern("logs", "*.log")
+    .dir("sub", ShapeBuilder::new().file("child.txt"))
+    .file("extra.txt")
+    .identifier("extra")
+    .optional()
+    .rest(ShapeRest::Forbid)
+    .build();
+  let inst = shape.create_at(&target.path)?;
+  assert_eq!(inst["a.txt"].file().unwrap().read_to_string()?, "hello");
+  assert!(inst["logs"].dir().unwrap().exists());
+  let sub = inst["sub"].shape().unwrap();
+  assert!(sub["child.txt"].file().unwrap().exists());
+  assert!(inst["extra"].file().is_none());
+  assert!(shape.validate(&target.path).is_ok());
+
+  target.create_file("undeclared.txt")?;
+  assert_eq!(shape.validate(&target.path).unwrap_err().len(), 1);
+  target.delete_file("undeclared.txt")?;
+
+  let opened = shape.open(&target.path).unwrap();
+  assert!(opened["a.txt"].file().is_some());
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[cfg(all(feature = "json", feature = "yaml", feature = "toml"))]
+#[test]
+fn schema_formats() -> error::Result<()> {
+  use fs_pro::shape::ShapeDef;
+
+  let yaml = r#"
+rest: forbid
+entries:
+  - type: file
+    name: config.toml
+    content: "name = 1"
+  - type: dir
+    name: logs
+    pattern: "*.log"
+  - type: dir
+    name: data
+    optional: true
+    entries:
+      - type: file
+        name: db.sqlite
+  - type: dirs
+    pattern: "tenant-*"
+    identifier: tenants
+    rest:
+      pattern: "*.md"
+"#;
+  let def = ShapeDef::from_yaml(yaml)?;
+  assert_eq!(ShapeDef::from_json(&def.to_json()?)?, def);
+  assert_eq!(ShapeDef::from_yaml(&def.to_yaml()?)?, def);
+  assert_eq!(ShapeDef::from_toml(&def.to_toml()?)?, def);
+
+  let target = Dir::temp_dir_rand()?;
+  let shape = def.build()?;
+  let inst = shape.create_at(&target.path)?;
+  assert_eq!(
+    inst["config.toml"].file().unwrap().read_to_string()?,
+    "name = 1"
+  );
+  target.create_dir("tenant-a")?.create_file("README.md")?;
+  assert!(shape.validate(&target.path).is_ok());
+  target.create_file("other.txt")?;
+  assert_eq!(shape.validate(&target.path).unwrap_err().len(), 1);
+  okay_to_err(target.delete());
+
+  let invalid = r#"{ "entries": [{ "type": "dir", "name": "a", "pattern": "[" }] }"#;
+  assert!(ShapeDef::from_json(invalid)?.build().is_err());
+
+  let exported = ShapeDef::describe::<Tenants>()?;
+  let schema = exported.clone().into_schema()?;
+  assert_eq!(ShapeDef::from_schema(&schema)?, exported);
+  assert_eq!(schema, Shape::<Tenants>::new().schema());
+  Ok(())
+}
+
+#[test]
+fn validate_report() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Strict> = Shape::new();
+  let strict = shape.create_at(&target.path)?;
+  assert!(shape.validate_report(&target.path).is_valid());
+
+  strict.hi_dir.hi.delete()?;
+  strict.hi.delete()?;
+  target.create_dir("hi.txt")?;
+  target.create_file("stray.txt")?;
+  let report = shape.validate_report(&target.path);
+  let violations: Vec<_> = report
+    .violations
+    .iter()
+    .map(|v| (v.identifier.as_str(), v.path.clone()))
+    .collect();
+  assert_eq!(
+    violations,
+    vec![
+      ("hi", target.path.join("hi.txt")),
+      ("hi_dir.hi", target.path.join("hi_dir/hi.txt")),
+      ("__rest", target.path.join("stray.txt")),
+    ]
+  );
+  assert!(matches!(
+    report.violations[0].kind,
+    ViolationKind::WrongType {
+      expected: EntryType::File,
+      found: EntryType::Dir
+    }
+  ));
+  assert!(matches!(
+    report.violations[1].kind,
+    ViolationKind::MissingFile
+  ));
+  assert!(matches!(
+    report.violations[2].kind,
+    ViolationKind::UnexpectedEntry {
+      entry_type: EntryType::File,
+      rest_pattern: None
+    }
+  ));
+
+  let tree = report.render_tree();
+  assert!(tree.contains("└── hi.txt  ✗ missing file [hi_dir.hi]"));
+  assert!(tree.contains("── stray.txt  ✗ undeclared file is not allowed [__rest]"));
+
+  #[cfg(feature = "json")]
+  {
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["violations"][1]["kind"], "missing_file");
+    assert_eq!(json["violations"][1]["identifier"], "hi_dir.hi");
+    assert_eq!(json["violations"][0]["found"], "folder");
+  }
+
+  assert_eq!(report.into_result().unwrap_err().len(), 3);
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn repair() -> error::Result<()> {
+  use fs_pro::shape::{RepairAction, RepairPolicy, ViolatingEntries};
+
+  let target = Dir::temp_dir_rand()?;
+  let quarantine = Dir::temp_dir_rand()?;
+  let shape: Shape<Strict> = Shape::new();
+  target.create_dir("hi.txt")?;
+  target.create_file("stray.txt")?;
+
+  let mut policy = RepairPolicy::new();
+  policy.violating = ViolatingEntries::MoveTo(quarantine.path.clone());
+  policy.dry_run = true;
+  let planned = shape.repair(&target.path, &policy)?;
+  let expected = vec![
+    RepairAction::Move(target.path.join("hi.txt"), quarantine.path.join("hi.txt")),
+    RepairAction::Move(
+      target.path.join("stray.txt"),
+      quarantine.path.join("stray.txt"),
+    ),
+    RepairAction::CreateFile(target.path.join("hi.txt")),
+    RepairAction::CreateDir(target.path.join("hi_dir")),
+    RepairAction::CreateFile(target.path.join("hi_dir/hi.txt")),
+  ];
+  assert_eq!(planned.actions, expected);
+  assert!(target.path.join("hi.txt").is_dir());
+  assert!(shape.validate(&target.path).is_err());
+
+  policy.dry_run = false;
+  let log = shape.repair(&target.path, &policy)?;
+  assert_eq!(log.actions, expected);
+  assert!(shape.validate(&target.path).is_ok());
+  assert!(quarantine.path.join("stray.txt").is_file());
+  assert!(shape.repair(&target.path, &policy)?.actions.is_empty());
+
+  target.create_file("stray.txt")?;
+  policy.violating = ViolatingEntries::Delete;
+  let log = shape.repair(&target.path, &policy)?;
+  assert_eq!(
+    log.actions,
+    vec![RepairAction::Delete(target.path.join("stray.txt"))]
+  );
+  assert!(shape.validate(&target.path).is_ok());
+
+  // a relative path is relative to the repaired directory and the folder isn't moved
+  target.create_file("stray.txt")?;
+  policy.violating = ViolatingEntries::MoveTo("quarantine/old".into());
+  let log = shape.repair(&target.path, &policy)?;
+  assert_eq!(
+    log.actions,
+    vec![RepairAction::Move(
+      target.path.join("stray.txt"),
+      target.path.join("quarantine/old/stray.txt")
+    )]
+  );
+  assert!(target.path.join("quarantine/old/stray.txt").is_file());
+  assert!(shape.repair(&target.path, &policy)?.actions.is_empty());
+
+  // a kept entry of the wrong type isn't repaired
+  let wrong = Dir::temp_dir_rand()?;
+  wrong.create_file("sub")?;
+  let shape = ShapeBuilder::new()
+    .file("a.txt")
+    .dir("sub", ShapeBuilder::new().file("child.txt"))
+    .build();
+  let mut policy = RepairPolicy::new();
+  policy.dry_run = true;
+  let planned = shape.repair(&wrong.path, &policy)?;
+  let expected = vec![RepairAction::CreateFile(wrong.path.join("a.txt"))];
+  assert_eq!(planned.actions, expected);
+  assert_eq!(planned.unrepairable, vec![wrong.path.join("sub")]);
+  policy.dry_run = false;
+  let log = shape.repair(&wrong.path, &policy)?;
+  assert_eq!(log.actions, expected);
+  assert_eq!(log.unrepairable, vec![wrong.path.join("sub")]);
+  assert!(wrong.path.join("sub").is_file());
+
+  okay_to_err(target.delete());
+  okay_to_err(quarantine.delete());
+  okay_to_err(wrong.delete());
+  Ok(())
+}
+
+#[test]
+fn create_at_rollback() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let existing = target.create_file("a.txt")?;
+  existing.write("old")?;
+  target.create_file("x")?;
+  let shape = ShapeBuilder::new()
+    .file("a.txt")
+    .content("new")
+    .dir("sub", ShapeBuilder::new().file("child.txt"))
+    .dir("x", ShapeBuilder::new())
+    .build();
+
+  let mut options = CreateOptions::new();
+  options.overwrite = true;
+  assert!(shape
+    .create_at_with_options(&target.path, &options)
+    .is_err());
+  assert_eq!(existing.read_to_string()?, "old");
+  assert!(!target.path.join("sub").exists());
+  assert!(target.path.join("x").is_file());
+  assert_eq!(target.read()?.len(), 2);
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn create_at_hook() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  target.create_file("a.txt")?;
+  let shape = ShapeBuilder::new()
+    .file("a.txt")
+    .file("b.txt")
+    .content("b")
+    .file("c.txt")
+    .optional()
+    .dir("sub", ShapeBuilder::new())
+    .build();
+
+  let mut events = vec![];
+  shape.create_at_hook(&target.path, &CreateOptions::new(), |event| {
+    events.push(event);
+    ShapeHookResult::Continue
+  })?;
+  assert_eq!(
+    events,
+    vec![
+      ShapeEvent::FileExisted(target.path.join("a.txt")),
+      ShapeEvent::FileCreated(target.path.join("b.txt")),
+      ShapeEvent::ContentWritten(target.path.join("b.txt")),
+      ShapeEvent::Skipped(target.path.join("c.txt")),
+      ShapeEvent::DirCreated(target.path.join("sub")),
+    ]
+  );
+
+  // aborting rolls back what was created
+  let other = Dir::temp_dir_rand()?;
+  let res = shape.create_at_hook(&other.path, &CreateOptions::new(), |event| match event {
+    ShapeEvent::DirCreated(_) => ShapeHookResult::Abort,
+    _ => ShapeHookResult::Continue,
+  });
+  assert!(res.is_err());
+  assert_eq!(other.read()?.len(), 0);
+
+  okay_to_err(target.delete());
+  okay_to_err(other.delete());
+  Ok(())
+}
+
+#[test]
+fn infer() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  target.create_file("Cargo.toml")?;
+  target.create_file("type")?;
+  target.create_dir("logs")?.create_file("a.log")?;
+  target.get_dir("logs")?.create_file("b.log")?;
+  target.create_dir("empty")?;
+  target.create_dir_all("src/bin")?.create_file("x.rs")?;
+  target.get_dir("src")?.create_file("main.rs")?;
+
+  let shape = DynShape::infer(&target)?;
+  assert!(shape.validate(&target.path).is_ok());
+  assert_eq!(
+    shape.to_rust("Layout")?,
+    r#"use fs_pro::{Dir, File, Shape};
+
+#[derive(Shape)]
+pub struct Layout {
+  #[name = "Cargo.toml"]
+  pub cargo_toml: File,
+  #[pattern = "*"]
+  pub empty: Dir,
+  #[pattern = "*.log"]
+  pub logs: Dir,
+  pub src: Src,
+  #[name = "type"]
+  pub type_: File,
+}
+
+#[derive(Shape)]
+pub struct Src {
+  pub bin: Bin,
+  #[name = "main.rs"]
+  pub main_rs: File,
+}
+
+#[derive(Shape)]
+pub struct Bin {
+  #[name = "x.rs"]
+  pub x_rs: File,
+}
+"#
+  );
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[test]
+fn infer_reserved_names() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  target.create_file("b")?;
+  target.create_dir("shape")?.create_file("a.txt")?;
+  target
+    .create_dir("vec")?
+    .create_dir("file")?
+    .create_file("c.txt")?;
+
+  let shape = DynShape::infer(&target)?;
+  assert_eq!(
+    shape.to_rust("Shape")?,
+    r#"use fs_pro::{File, Shape};
+
+#[derive(Shape)]
+pub struct Shape_2 {
+  pub b: File,
+  pub shape: Shape_3,
+  pub vec: Vec_2,
+}
+
+#[derive(Shape)]
+pub struct Shape_3 {
+  #[name = "a.txt"]
+  pub a_txt: File,
+}
+
+#[derive(Shape)]
+pub struct Vec_2 {
+  pub file: File_2,
+}
+
+#[derive(Shape)]
+pub struct File_2 {
+  #[name = "c.txt"]
+  pub c_txt: File,
+}
+"#
+  );
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+// the source generated by `to_rust` for an empty folder
+#[derive(Shape)]
+pub struct EmptyLayout {}
+
+#[test]
+fn infer_empty() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape = DynShape::infer(&target)?;
+  assert_eq!(
+    shape.to_rust("EmptyLayout")?,
+    "use fs_pro::Shape;\n\n#[derive(Shape)]\npub struct EmptyLayout {}\n"
+  );
+  // the generated struct has the inferred schema
+  assert_eq!(
+    &schema_from_static(EmptyLayout::shape_describe()),
+    shape.schema()
+  );
+  assert!(Shape::<EmptyLayout>::new().validate(&target.path).is_ok());
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct LayoutV1 {
+  #[name = "config.toml"]
+  pub config: File,
+  #[pattern = "*.txt"]
+  pub logs: Dir,
+  #[name = "old.txt"]
+  pub old: File,
+  pub hi_dir: H,
+}
+
+#[derive(Shape)]
+#[rest = "forbid"]
+struct LayoutV2 {
+  #[name = "settings.toml"]
+  pub config: File,
+  #[pattern = "*.log"]
+  pub logs: Dir,
+  #[name = "new.txt"]
+  pub new: Option<File>,
+  pub hi_dir: H,
+}
+
+#[test]
+fn diff() -> error::Result<()> {
+  let diff = ShapeDiff::between_types::<LayoutV1, LayoutV2>()?;
+  let changes: Vec<_> = diff
+    .changes
+    .iter()
+    .map(|change| (change.path.to_str().unwrap(), &change.kind))
+    .collect();
+  assert_eq!(
+    changes,
+    vec![
+      (
+        "",
+        &ChangeKind::RestChanged {
+          from: ShapeRest::Allow,
+          to: ShapeRest::Forbid
+        }
+      ),
+      (
+        "logs",
+        &ChangeKind::PatternChanged {
+          from: Some("*.txt".to_string()),
+          to: Some("*.log".to_string())
+        }
+      ),
+      (
+        "settings.toml",
+        &ChangeKind::Renamed {
+          from: "config.toml".into()
+        }
+      ),
+      (
+        "old.txt",
+        &ChangeKind::Removed {
+          entry_type: EntryType::File
+        }
+      ),
+      (
+        "new.txt",
+        &ChangeKind::Added {
+          entry_type: EntryType::File
+        }
+      ),
+    ]
+  );
+  assert!(ShapeDiff::between_types::<LayoutV2, LayoutV2>()?.is_empty());
+
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<LayoutV1> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert!(shape.diff_dir(&target.path)?.is_empty());
+  inst.old.delete()?;
+  inst.logs.create_file("a.md")?;
+  target.create_file("extra")?;
+  let diff = shape.diff_dir(&target.path)?;
+  assert_eq!(
+    diff.to_string(),
+    "logs/a.md: added file [logs]\nold.txt: removed file [old]\nextra: added file [__rest]\n"
+  );
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+impl Migrate<LayoutV2> for LayoutV1 {
+  fn migration() -> Migration {
+    let diff = ShapeDiff::between_types::<LayoutV1, LayoutV2>().unwrap();
+    Migration::from_diff(&diff).delete("old.txt")
+  }
+}
+
+#[test]
+fn migrate() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let v1 = Shape::<LayoutV1>::new().create_at(&target.path)?;
+  v1.config.write("name = \"app\"")?;
+  let v2: Shape<LayoutV2> = Shape::new();
+
+  // a stray file makes the final validation fail, every step is rolled back
+  target.create_file("stray")?;
+  assert!(v2.migrate::<LayoutV1>(&target.path).is_err());
+  assert_eq!(v1.config.read_to_string()?, "name = \"app\"");
+  assert!(v1.old.exists());
+  assert!(!target.path.join("settings.toml").exists());
+  assert!(Shape::<LayoutV1>::new().validate(&target.path).is_ok());
+
+  target.delete_file("stray")?;
+  let migrated = v2.migrate::<LayoutV1>(&target.path)?;
+  assert_eq!(migrated.config.path, target.path.join("settings.toml"));
+  assert_eq!(migrated.config.read_to_string()?, "name = \"app\"");
+  assert!(!v1.old.exists());
+  assert!(migrated.new.is_none());
+  assert!(migrated.logs.exists() && migrated.hi_dir.hi.exists());
+  assert_eq!(v1.hi_dir.hi.path, migrated.hi_dir.hi.path);
+  let parent = target.path.parent().unwrap();
+  let backups = Dir::new(parent)?
+    .read_as_osstring_vec()?
+    .iter()
+    .filter(|name| name.to_string_lossy().contains("migration-backup"))
+    .count();
+  assert_eq!(backups, 0);
+
+  // steps of a dynamic migration
+  let other = Dir::temp_dir_rand()?;
+  let data = other.create_dir("data")?;
+  data.create_file("a.log")?;
+  data.create_file("b.csv")?;
+  other.create_file("notes.txt")?;
+  let shape = ShapeBuilder::new()
+    .dir_pattern("logs", "*.log")
+    .dir_pattern("tables", "*.csv")
+    .dir("docs", ShapeBuilder::new().file("notes.txt"))
+    .build();
+  let migration = Migration::new()
+    .split("data", &[("*.log", "logs"), ("*.csv", "tables")])
+    .move_into("notes.txt", "docs");
+  shape.migrate(&other.path, &migration)?;
+  assert!(other.path.join("logs/a.log").is_file());
+  assert!(other.path.join("tables/b.csv").is_file());
+  assert!(other.path.join("docs/notes.txt").is_file());
+  assert!(!other.path.join("data").exists());
+
+  okay_to_err(target.delete());
+  okay_to_err(other.delete());
+  Ok(())
+}
+
+fn starts_with_name(file: &File) -> error::Result<()> {
+  if file.read_to_string()?.starts_with("name") {
+    Ok(())
+  } else {
+    Err(error::Error::new(
+      error::ErrorKind::InvalidFile,
+      "config must start with name",
+    ))
+  }
+}
+
+#[derive(Shape)]
+struct Checked {
+  #[name = "config.toml"]
+  #[non_empty]
+  #[max_size = 16]
+  #[validate_with = "starts_with_name"]
+  pub config: File,
+  #[name = "data.json"]
+  #[utf8]
+  #[cfg_attr(feature = "json", json)]
+  pub data: File,
+  #[name = "small.bin"]
+  #[min_size = 2]
+  pub small: Option<File>,
+}
+
+#[test]
+fn content_validators() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Checked> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert!(inst.small.is_none());
+  inst.config.write("name = \"a\"")?;
+  inst.data.write("{\"a\": 1}")?;
+  assert!(shape.validate(&target.path).is_ok());
+
+  inst.config.write("")?;
+  inst.data.write(&[0xff, 0xfe][..])?;
+  target.create_file("small.bin")?.write("x")?;
+  let messages: Vec<_> = shape
+    .validate_report(&target.path)
+    .violations
+    .into_iter()
+    .map(|violation| {
+      assert!(matches!(violation.kind, ViolationKind::InvalidContent(_)));
+      (violation.identifier.clone(), violation.message())
+    })
+    .collect();
+  assert_eq!(messages.len(), if cfg!(feature = "json") { 5 } else { 4 });
+  assert_eq!(
+    messages[0],
+    ("config".to_string(), "file is empty".to_string())
+  );
+  assert_eq!(
+    messages[1],
+    (
+      "config".to_string(),
+      "config must start with name".to_string()
+    )
+  );
+  assert_eq!(messages[2].0, "data");
+  assert!(messages[2].1.starts_with("file is not valid utf-8"));
+  #[cfg(feature = "json")]
+  assert!(messages[3].1.starts_with("file is not valid json"));
+  assert_eq!(
+    messages.last().unwrap().1,
+    "file is 1 bytes, less than 2 bytes"
+  );
+
+  inst.config.write("name = \"a very long name\"")?;
+  let errors = shape.validate(&target.path).unwrap_err();
+  assert!(matches!(errors[0].kind, error::ErrorKind::InvalidFile));
+  assert_eq!(errors[0].message, "file is 25 bytes, more than 16 bytes");
+
+  let dyn_shape = ShapeBuilder::new()
+    .file("config.toml")
+    .validator(ContentValidator::NonEmpty)
+    .build();
+  assert!(dyn_shape.validate(&target.path).is_ok());
+
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct Deploy {
+  #[name = "secret.key"]
+  #[mode = 0o600]
+  pub secret: File,
+  #[name = "run.sh"]
+  #[executable]
+  pub run: File,
+  #[readonly]
+  pub config: H,
+}
+
+#[cfg(unix)]
+#[test]
+fn modes() -> error::Result<()> {
+  use std::os::unix::fs::PermissionsExt;
+
+  let mode_of =
+    |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Deploy> = Shape::new();
+  let inst = shape.create_at(&target.path)?;
+  assert_eq!(mode_of(&inst.secret.path), 0o600);
+  assert_eq!(mode_of(&inst.run.path) & 0o100, 0o100);
+  assert_eq!(mode_of(&target.path.join("config")) & 0o222, 0);
+  assert!(inst.config.hi.exists());
+  assert!(shape.validate(&target.path).is_ok());
+
+  let permissions = std::fs::Permissions::from_mode(0o644);
+  std::fs::set_permissions(&inst.secret.path, permissions).unwrap();
+  let report = shape.validate_report(&target.path);
+  assert_eq!(report.violations.len(), 1);
+  let violation = &report.violations[0];
+  assert!(matches!(
+    violation.kind,
+    ViolationKind::WrongMode {
+      actual: 0o644,
+      entry_type: EntryType::File,
+      ..
+    }
+  ));
+  assert_eq!(violation.message(), "mode is 0644, expected 0600");
+
+  // repair fixes the mode of existing entries and applies it to the created ones
+  use fs_pro::shape::{RepairAction, RepairPolicy};
+  let log = shape.repair(&target.path, &RepairPolicy::new())?;
+  assert!(
+    matches!(&log.actions[..], [RepairAction::SetMode(path, _)] if *path == inst.secret.path)
+  );
+  assert_eq!(mode_of(&inst.secret.path), 0o600);
+  let repaired = Dir::temp_dir_rand_no_create()?;
+  shape.repair(&repaired.path, &RepairPolicy::new())?;
+  assert!(shape.validate(&repaired.path).is_ok());
+  std::fs::set_permissions(
+    repaired.path.join("config"),
+    std::fs::Permissions::from_mode(0o755),
+  )
+  .unwrap();
+  okay_to_err(repaired.delete());
+
+  let permissions = std::fs::Permissions::from_mode(0o755);
+  std::fs::set_permissions(target.path.join("config"), permissions).unwrap();
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct AppLayout {
+  #[name = "{app}.conf"]
+  pub config: File,
+  #[name = "{app}-{env}"]
+  #[pattern = "*.log"]
+  pub logs: Dir,
+  #[name = "{{literal}}.txt"]
+  pub literal: File,
+  #[name = "it's \"quoted\".txt"]
+  pub quoted: File,
+}
+
+#[derive(Shape)]
+struct Braces {
+  #[name = "{{literal}}.txt"]
+  pub literal: File,
+}
+
+#[test]
+fn parameterized_names() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<AppLayout> = Shape::new();
+  let mut ctx = ShapeContext::new();
+  ctx.insert("app".to_string(), "nginx".to_string());
+  ctx.insert("env".to_string(), "prod".to_string());
+  let inst = shape.create_at_with(&target.path, &ctx)?;
+  assert_eq!(inst.config.path, target.path.join("nginx.conf"));
+  assert_eq!(inst.logs.path, target.path.join("nginx-prod"));
+  assert_eq!(inst.literal.path, target.path.join("{literal}.txt"));
+  assert_eq!(inst.quoted.path, target.path.join("it's \"quoted\".txt"));
+  assert!(shape.validate_with(&target.path, &ctx).is_ok());
+  assert!(shape.open_with(&target.path, &ctx).is_ok());
+
+  // the names don't match another app
+  ctx.insert("app".to_string(), "redis".to_string());
+  let errors = shape.validate_with(&target.path, &ctx).unwrap_err();
+  assert_eq!(errors.len(), 2);
+
+  ctx.remove("env");
+  let e = shape.create_at_with(&target.path, &ctx).err().unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidSchema));
+  assert!(!target.path.join("redis.conf").exists());
+
+  ctx.insert("env".to_string(), "../escape".to_string());
+  let e = shape.create_at_with(&target.path, &ctx).err().unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidFileName));
+
+  let dyn_shape = ShapeBuilder::new().file("{app}.toml").build();
+  let dyn_inst = dyn_shape.create_at_with(&target.path, &ctx)?;
+  assert!(target.path.join("redis.toml").exists());
+  assert!(dyn_inst["{app}.toml"].file().is_some());
+  assert!(dyn_shape.validate_with(&target.path, &ctx).is_ok());
+
+  // the methods without a context reject placeholders instead of using them as names
+  let e = shape.create_at(&target.path).err().unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidSchema));
+  assert!(e.to_string().contains("\"app\""));
+  assert!(!target.path.join("{app}.conf").exists());
+  let errors = shape.validate(&target.path).unwrap_err();
+  assert!(matches!(errors[0].kind, error::ErrorKind::InvalidSchema));
+  assert!(shape.open(&target.path).is_err());
+  let report = shape.validate_report(&target.path);
+  assert!(matches!(
+    report.violations[0].kind,
+    ViolationKind::Unreadable(_)
+  ));
+  assert!(dyn_shape.create_at(&target.path).is_err());
+  let e = ShapeDiff::between_types::<Empty, AppLayout>()
+    .err()
+    .unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidSchema));
+
+  // and unescape doubled braces like the methods with a context
+  let diff = ShapeDiff::between_types::<Empty, Braces>()?;
+  assert_eq!(diff.changes[0].path, std::path::Path::new("{literal}.txt"));
+  let empty = Dir::temp_dir_rand()?;
+  let drift = Shape::<Braces>::new().diff_dir(&empty.path)?;
+  assert_eq!(drift.changes[0].path, diff.changes[0].path);
+  let braces = Shape::<Braces>::new().create_at(&empty.path)?;
+  assert_eq!(braces.literal.path, empty.path.join(&drift.changes[0].path));
+  okay_to_err(empty.delete());
+  let literal = Dir::temp_dir_rand()?;
+  let dyn_shape = ShapeBuilder::new().file("{{literal}}.txt").build();
+  let dyn_inst = dyn_shape.create_at(&literal.path)?;
+  assert_eq!(
+    dyn_inst["{{literal}}.txt"].file().unwrap().path,
+    literal.path.join("{literal}.txt")
+  );
+  assert!(dyn_shape.validate(&literal.path).is_ok());
+  // a resolved shape and an inferred one use their names as they are
+  let resolved = dyn_shape.resolve(&ctx)?;
+  assert!(resolved.validate(&literal.path).is_ok());
+  let inferred = DynShape::infer(&literal)?;
+  assert!(inferred.validate(&literal.path).is_ok());
+
+  okay_to_err(literal.delete());
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct PythonPackage {
+  #[root]
+  pub root: Dir,
+  #[name = "setup.py"]
+  pub setup: File,
+  #[name = "__init__.py"]
+  pub init: File,
+}
+
+#[derive(Shape)]
+struct RustCrate {
+  #[root]
+  pub root: Dir,
+  #[name = "Cargo.toml"]
+  pub manifest: File,
+  #[pattern = "*.rs"]
+  pub src: Dir,
+}
+
+#[derive(Shape)]
+#[instance]
+enum Package {
+  Python(PythonPackage),
+  Rust(RustCrate),
+}
+
+#[derive(Shape)]
+struct Workspace {
+  #[root]
+  pub root: Dir,
+  #[pattern = "*"]
+  pub packages: Vec<Package>,
+}
+
+#[test]
+fn enum_shapes() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<Package> = Shape::new();
+  // the first variant is created
+  match shape.create_at(&target.path)? {
+    Package::Python(package) => assert!(package.setup.exists() && package.init.exists()),
+    Package::Rust(_) => panic!("expected the python variant"),
+  }
+  okay_to_err(target.delete());
+
+  target.create()?;
+  target.create_file("Cargo.toml")?;
+  target.create_dir("src")?;
+  assert!(shape.validate(&target.path).is_ok());
+  match shape.open(&target.path).unwrap() {
+    Package::Rust(package) => {
+      assert_eq!(package.manifest.path, target.path.join("Cargo.toml"));
+      assert_eq!(package.src.path, target.path.join("src"));
+    }
+    Package::Python(_) => panic!("expected the rust variant"),
+  }
+  // create_at keeps the matching variant
+  assert!(matches!(shape.create_at(&target.path)?, Package::Rust(_)));
+  assert!(!target.path.join("setup.py").exists());
+  let source = DynShape::new(shape.schema()).to_rust("Package")?;
+  assert!(source.contains("pub enum Package {\n  Python(PackagePython),\n  Rust(PackageRust),\n}"));
+
+  // the errors are the errors of the closest variant
+  std::fs::remove_dir(target.path.join("src")).unwrap();
+  let report = shape.validate_report(&target.path);
+  assert_eq!(report.violations.len(), 1);
+  assert_eq!(report.violations[0].path, target.path.join("src"));
+  assert!(shape.open(&target.path).is_err());
+  okay_to_err(target.delete());
+
+  let workspace = Dir::temp_dir_rand()?;
+  workspace.create_dir("py")?.create_file("setup.py")?;
+  workspace.get_file("py/__init__.py")?.create()?;
+  workspace.create_dir("rs")?.create_file("Cargo.toml")?;
+  workspace.create_dir("rs/src")?;
+  let inst = Shape::<Workspace>::new().open(&workspace.path).unwrap();
+  assert!(matches!(inst.packages[0], Package::Python(_)));
+  assert!(matches!(inst.packages[1], Package::Rust(_)));
+  assert_eq!(inst.root.path, workspace.path);
+
+  let dyn_shape = ShapeBuilder::new()
+    .variant("python", ShapeBuilder::new().file("setup.py"))
+    .variant("rust", ShapeBuilder::new().file("Cargo.toml"))
+    .build();
+  let dyn_inst = dyn_shape.open(workspace.path.join("rs")).unwrap();
+  assert_eq!(dyn_inst["__variants"].variant().unwrap().0, "rust");
+
+  // an instance that doesn't match the shape is an error, not a panic
+  use fs_pro::shape::{ShapeDescribe, ShapeInstItem};
+  let e = Package::shape_new(vec![ShapeInstItem::Missing])
+    .err()
+    .unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidFolder));
+  let e = RustCrate::shape_new(vec![]).err().unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::InvalidFolder));
+  okay_to_err(workspace.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct RootedTest {
+  #[root]
+  pub root: Dir,
+  #[name = "hi.txt"]
+  pub hi: File,
+  #[pattern = "*.txt"]
+  pub my_dir: Dir,
+  pub hi_dir: H,
+}
+
+#[test]
+fn instance_ops() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  let shape: Shape<RootedTest> = Shape::new();
+  assert_eq!(shape.create_at(&target.path)?.root().path, target.path);
+  let inst = shape.create_at(&target.path)?;
+  inst.hi.write("hello")?;
+  inst.my_dir.create_file("a.txt")?;
+  // undeclared entries stay where they are
+  inst.my_dir.create_file("b.md")?;
+  target.create_file("notes.md")?;
+
+  let dest = Dir::temp_dir_rand_no_create()?;
+  let copy = inst.copy(&dest.path)?;
+  assert_eq!(copy.root().path, dest.path);
+  assert_eq!(copy.hi.read_to_string()?, "hello");
+  assert!(copy.my_dir.entry_exists("a.txt"));
+  assert!(!copy.my_dir.entry_exists("b.md"));
+  assert!(copy.hi_dir.hi.exists());
+  assert!(!dest.entry_exists("notes.md"));
+  assert!(inst.hi.exists());
+
+  // nothing is copied over existing files
+  let e = inst.copy(&dest.path).err().unwrap();
+  assert!(matches!(e.kind, error::ErrorKind::AlreadyExists));
+  // the missing parents of dest are removed again when the copy fails
+  let nested = Dir::temp_dir_rand_no_create()?;
+  assert!(inst
+    .copy(nested.path.join("a").join("x".repeat(300)))
+    .is_err());
+  assert!(!nested.exists());
+  copy.delete()?;
+  assert!(!dest.exists());
+
+  let moved = inst.move_to(&dest.path)?;
+  assert_eq!(moved.hi.read_to_string()?, "hello");
+  assert!(moved.my_dir.entry_exists("a.txt"));
+  assert!(!target.entry_exists("hi.txt"));
+  assert!(!target.entry_exists("hi_dir"));
+  assert!(target.get_dir("my_dir")?.entry_exists("b.md"));
+  assert!(target.entry_exists("notes.md"));
+
+  let package = Dir::temp_dir_rand()?;
+  package.create_file("Cargo.toml")?;
+  package.create_dir("src")?.create_file("lib.rs")?;
+  let opened = Shape::<Package>::new().open(&package.path).unwrap();
+  assert_eq!(opened.root().path, package.path);
+  opened.delete()?;
+  assert!(!package.exists());
+
+  // the root is known even if no entry exists
+  let empty = Dir::temp_dir_rand()?;
+  let workspace = Shape::<Workspace>::new().open(&empty.path).unwrap();
+  assert!(workspace.packages.is_empty());
+  assert_eq!(workspace.root().path, empty.path);
+  let empty_copy = Dir::temp_dir_rand_no_create()?;
+  let copy = workspace.copy(&empty_copy.path)?;
+  assert_eq!(copy.root.path, empty_copy.path);
+  copy.delete()?;
+  assert!(!empty_copy.exists());
+  workspace.delete()?;
+  assert!(!empty.exists());
+
+  okay_to_err(moved.root().delete());
+  okay_to_err(target.delete());
+  Ok(())
+}
+
+#[derive(Shape)]
+struct Empty {}
+
+#[derive(Shape)]
+#[rest = "forbid"]
+struct Nothing {}
+
+#[derive(Shape)]
+struct AllOptional {
+  #[name = "notes.txt"]
+  pub notes: Option<File>,
+  #[pattern = "*"]
+  pub packages: Vec<H>,
+}
+
+#[test]
+fn no_required_entries() -> error::Result<()> {
+  let target = Dir::temp_dir_rand()?;
+  Shape::<Empty>::new().create_at(&target.path)?;
+  assert!(Shape::<Empty>::new().validate(&target.path).is_ok());
+  assert!(Shape::<Nothing>::new().open(&target.path).is_ok());
+  let inst = Shape::<AllOptional>::new().open(&target.path).unwrap();
+  assert!(inst.notes.is_none());
+  assert!(inst.packages.is_empty());
+  target.create_file("stray.txt")?;
+  assert!(Shape::<Nothing>::new().validate(&target.path).is_err());
+
+  okay_to_err(target.delete());
+  Ok(())
+}